@@ -1,4 +1,4 @@
-use crate::{vector, Intersection, Material, Object, Vec4};
+use crate::{cube_map, point, vector, CubeFace, Intersection, Material, Object, Uv, Vec4};
 
 pub struct Aabb {
     pub min: Vec4,
@@ -38,22 +38,78 @@ impl Aabb {
             -normal
         }
     }
+
+    /// object space puts the min corner at the origin
+    pub fn to_object_space(&self, p: Vec4) -> Vec4 {
+        p - (self.min - point(0.0, 0.0, 0.0))
+    }
+
+    /// direction of increasing u on the face `p` lies on (see `cube_map`)
+    pub fn tangent_at(&self, p: Vec4) -> Vec4 {
+        match cube_map(self.to_unit(p)).0 {
+            CubeFace::Right => vector(0.0, 0.0, 1.0),
+            CubeFace::Left => vector(0.0, 0.0, -1.0),
+            CubeFace::Back => vector(-1.0, 0.0, 0.0),
+            CubeFace::Front | CubeFace::Up | CubeFace::Down => vector(1.0, 0.0, 0.0),
+        }
+    }
+
+    /// uv on the face `p` lies on, each face covering the whole [0, 1] range
+    pub fn uv_at(&self, p: Vec4) -> Uv {
+        cube_map(self.to_unit(p)).1
+    }
+
+    /// box stretched to [-1, 1]^3
+    fn to_unit(&self, p: Vec4) -> Vec4 {
+        let mut unit = point(0.0, 0.0, 0.0);
+        for i in 0..3 {
+            unit[i] = (p[i] - self.min[i]) / (self.max[i] - self.min[i]) * 2.0 - 1.0;
+        }
+        unit
+    }
 }
 
 fn nearly_close(a: f32, b: f32) -> bool {
     let diff = (a - b).abs();
-    return diff < 0.001;
+    diff < 0.001
 }
 
 impl Object for Aabb {
-    fn ray_intersect(&self, ray: &crate::Ray) -> Vec<crate::Intersection> {
+    fn ray_intersect(&self, ray: &crate::Ray) -> Vec<crate::Intersection<'_>> {
         let ts = ray.intersect_aabb(self);
         ts.iter()
             .map(|t| {
                 let pos = ray.position(*t);
                 let normalv = self.normal_at(pos);
-                Intersection::new(*t, pos, normalv, &self.mat)
+                let mut i = Intersection::new(*t, pos, normalv, &self.mat);
+                i.object_pos = self.to_object_space(pos);
+                i.uv = self.uv_at(pos);
+                i.tangent = self.tangent_at(pos);
+                i
             })
             .collect()
     }
+
+    fn name(&self) -> &'static str {
+        "aabb"
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::lib_test::assert_almost_eq_f32;
+
+    use super::*;
+
+    #[test]
+    fn uv_per_face() {
+        let aabb = Aabb::new(point(0.0, 0.0, 0.0), point(4.0, 2.0, 2.0), true);
+        let (u, v) = aabb.uv_at(point(1.0, 1.5, 0.0));
+        assert_almost_eq_f32(u, 0.25);
+        assert_almost_eq_f32(v, 0.75);
+
+        let (u, v) = aabb.uv_at(point(4.0, 0.5, 1.5));
+        assert_almost_eq_f32(u, 0.75);
+        assert_almost_eq_f32(v, 0.25);
+    }
 }
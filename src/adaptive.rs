@@ -0,0 +1,191 @@
+use crate::{render_sample, Camera, Canvas, Color, Rng, World};
+
+#[derive(Debug, Clone, Copy)]
+pub struct AdaptiveSettings {
+    /// at least 2, as one sample has no variance. smaller values are raised to 2
+    pub min_samples: u32,
+    /// raised to `min_samples` when below it
+    pub max_samples: u32,
+    /// a pixel is converged when the standard error of its mean luminance drops below this
+    pub threshold: f32,
+}
+
+impl Default for AdaptiveSettings {
+    fn default() -> Self {
+        Self {
+            min_samples: 4,
+            max_samples: 64,
+            threshold: 0.005,
+        }
+    }
+}
+
+impl AdaptiveSettings {
+    /// the settings `render_adaptive` actually uses, with the sample counts raised into range
+    pub fn clamped(self) -> Self {
+        let min_samples = self.min_samples.max(2);
+        Self {
+            min_samples,
+            max_samples: self.max_samples.max(min_samples),
+            ..self
+        }
+    }
+}
+
+pub struct AdaptiveResult {
+    pub canvas: Canvas,
+    pub sample_counts: Vec<u32>,
+    /// the settings used, clamped (see `AdaptiveSettings::clamped`)
+    pub settings: AdaptiveSettings,
+}
+
+impl AdaptiveResult {
+    pub fn sample_count_at(&self, x: u32, y: u32) -> u32 {
+        let (width, _) = self.canvas.get_size();
+        self.sample_counts[(x + y * width) as usize]
+    }
+
+    /// samples per pixel as an image. blue = min_samples, red = max_samples
+    pub fn heatmap(&self) -> Canvas {
+        let (width, height) = self.canvas.get_size();
+        let mut heatmap = Canvas::new(width, height, Some(1.0));
+
+        let min = self.settings.min_samples as f32;
+        let range = (self.settings.max_samples as f32 - min).max(1.0);
+        for y in 0..height {
+            for x in 0..width {
+                let t = (self.sample_count_at(x, y) as f32 - min) / range;
+                heatmap.write_pixel(x, y, Color::new(t, 0.0, 1.0 - t));
+            }
+        }
+
+        heatmap
+    }
+}
+
+/// renders each pixel with between min_samples and max_samples jittered samples,
+/// stopping early once the pixel's variance says more samples would not change it
+pub fn render_adaptive(
+    world: &World,
+    camera: &Camera,
+    settings: AdaptiveSettings,
+    gamma: Option<f32>,
+) -> AdaptiveResult {
+    let settings = settings.clamped();
+
+    let mut canvas = Canvas::new(camera.hsize, camera.vsize, gamma);
+    let mut sample_counts = vec![0; camera.hsize as usize * camera.vsize as usize];
+
+    for iy in 0..camera.vsize {
+        for ix in 0..camera.hsize {
+            let idx = ix + iy * camera.hsize;
+            // seed per pixel so the result does not depend on render order
+            let mut rng = Rng::new(idx as u64);
+
+            let mut sum = Color::BLACK;
+            // welford's running variance of luminance
+            let mut mean = 0.0;
+            let mut m2 = 0.0;
+            let mut n = 0;
+
+            while n < settings.max_samples {
+                let (x, y) = if n == 0 {
+                    (ix as f32, iy as f32)
+                } else {
+                    (ix as f32 + rng.next_f32() - 0.5, iy as f32 + rng.next_f32() - 0.5)
+                };
+                let color = render_sample(world, camera, x, y);
+                sum += color;
+                n += 1;
+
+                let lum = color.luminance();
+                let delta = lum - mean;
+                mean += delta / n as f32;
+                m2 += delta * (lum - mean);
+
+                if n >= settings.min_samples {
+                    let variance = m2 / (n - 1) as f32;
+                    let std_error = (variance / n as f32).sqrt();
+                    if std_error < settings.threshold {
+                        break;
+                    }
+                }
+            }
+
+            canvas.write_pixel(ix, iy, sum * (1.0 / n as f32));
+            sample_counts[idx as usize] = n;
+        }
+    }
+
+    if let Some(outline) = &world.outline {
+        outline.draw(world, camera, &mut canvas);
+    }
+
+    AdaptiveResult {
+        canvas,
+        sample_counts,
+        settings,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{point, vector, view_transform, Angle, Sphere};
+
+    use super::*;
+
+    fn test_camera() -> Camera {
+        let view_mat = view_transform(point(0.0, 0.0, -5.0), point(0.0, 0.0, 0.0), vector(0.0, 1.0, 0.0));
+        Camera::new(15, 15, Angle::from_degree(60.0), 1.0, view_mat)
+    }
+
+    #[test]
+    fn flat_image_uses_min_samples() {
+        // camera inside a sphere lit only by ambient sees one flat color
+        let mut world = World::new();
+        let mut sphere = Sphere::new(point(0.0, 0.0, -5.0), 100.0);
+        sphere.mat.diffuse = 0.0;
+        sphere.mat.specular = 0.0;
+        world.add_object(sphere);
+
+        let settings = AdaptiveSettings::default();
+        let result = render_adaptive(&world, &test_camera(), settings, None);
+
+        assert!(result.sample_counts.iter().all(|n| *n == settings.min_samples));
+    }
+
+    #[test]
+    fn edges_get_more_samples() {
+        let world = World::default();
+        let settings = AdaptiveSettings::default();
+        let result = render_adaptive(&world, &test_camera(), settings, None);
+
+        // background corner converges immediately, sphere silhouette does not
+        assert_eq!(result.sample_count_at(0, 0), settings.min_samples);
+        assert!(result.sample_counts.iter().any(|n| *n > settings.min_samples));
+        assert!(result.sample_counts.iter().all(|n| *n <= settings.max_samples));
+    }
+
+    #[test]
+    fn sample_counts_are_clamped() {
+        let settings = AdaptiveSettings {
+            min_samples: 1,
+            max_samples: 0,
+            ..Default::default()
+        };
+        let result = render_adaptive(&World::default(), &test_camera(), settings, None);
+
+        assert_eq!((result.settings.min_samples, result.settings.max_samples), (2, 2));
+        assert!(result.sample_counts.iter().all(|n| *n == 2));
+    }
+
+    #[test]
+    fn heatmap_has_canvas_size() {
+        let world = World::default();
+        let result = render_adaptive(&world, &test_camera(), AdaptiveSettings::default(), None);
+        let heatmap = result.heatmap();
+
+        assert_eq!(heatmap.get_size(), (15, 15));
+        assert_eq!(heatmap.pixel_at(0, 0).blue, 1.0);
+    }
+}
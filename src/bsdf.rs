@@ -0,0 +1,348 @@
+use std::f32::consts::PI;
+use std::fmt;
+
+use crate::{
+    cook_torrance, cook_torrance_anisotropic, ggx_distribution, oren_nayar, vector, Color, Material, ShadingFrame,
+    Vec4, MIN_ROUGHNESS,
+};
+
+/// how a surface scatters light: the material's shading model.
+/// `lightv` and `viewv` are unit vectors from the surface toward the light and the eye.
+/// `material` and `color` (the surface color at the point) carry the parameters shared by all models
+pub trait Bsdf: fmt::Debug {
+    /// brdf times the cosine at the light: the share of light arriving from `lightv` that leaves toward `viewv`
+    fn evaluate(&self, material: &Material, color: Color, frame: &ShadingFrame, lightv: Vec4, viewv: Vec4) -> Color;
+
+    /// picks a light direction for `viewv` from two uniform numbers in [0, 1).
+    /// None when the direction picked ends up below the surface
+    fn sample(
+        &self,
+        material: &Material,
+        color: Color,
+        frame: &ShadingFrame,
+        viewv: Vec4,
+        u: (f32, f32),
+    ) -> Option<BsdfSample> {
+        let lightv = cosine_hemisphere(frame, u);
+        bsdf_sample(self, material, color, frame, lightv, viewv)
+    }
+
+    /// light the model adds once per shading point, whatever the lights, such as `Toon`'s rim.
+    /// none by default
+    fn unlit(&self, _frame: &ShadingFrame, _viewv: Vec4) -> Color {
+        Color::BLACK
+    }
+
+    /// probability density (per solid angle) of `sample` picking `lightv`
+    fn pdf(&self, _material: &Material, frame: &ShadingFrame, lightv: Vec4, _viewv: Vec4) -> f32 {
+        frame.normalv.dot(lightv).max(0.0) / PI
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct BsdfSample {
+    pub lightv: Vec4,
+    /// `evaluate / pdf`: what light from `lightv` is multiplied with in a monte carlo estimate
+    pub weight: Color,
+    pub pdf: f32,
+}
+
+/// cosine weighted direction on the hemisphere around the frame's normal
+fn cosine_hemisphere(frame: &ShadingFrame, (u1, u2): (f32, f32)) -> Vec4 {
+    let r = u1.sqrt();
+    let phi = 2.0 * PI * u2;
+    frame.to_world(vector(r * phi.cos(), r * phi.sin(), (1.0 - u1).max(0.0).sqrt()))
+}
+
+fn bsdf_sample<B: Bsdf + ?Sized>(
+    bsdf: &B,
+    material: &Material,
+    color: Color,
+    frame: &ShadingFrame,
+    lightv: Vec4,
+    viewv: Vec4,
+) -> Option<BsdfSample> {
+    let pdf = bsdf.pdf(material, frame, lightv, viewv);
+    if frame.normalv.dot(lightv) <= 0.0 || pdf <= 0.0 {
+        return None;
+    }
+
+    let weight = bsdf.evaluate(material, color, frame, lightv, viewv) * (1.0 / pdf);
+    Some(BsdfSample { lightv, weight, pdf })
+}
+
+/// lambert (or oren-nayar, see `Material::diffuse_roughness`) diffuse plus a phong highlight.
+/// uses `Material::diffuse`, `specular` and `shininess`
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Phong;
+
+impl Phong {
+    /// share of samples spent on the highlight, the rest are cosine weighted for the diffuse term
+    fn specular_share(material: &Material) -> f32 {
+        let total = material.diffuse + material.specular;
+        if total > 0.0 {
+            material.specular / total
+        } else {
+            0.0
+        }
+    }
+}
+
+impl Bsdf for Phong {
+    fn evaluate(&self, material: &Material, color: Color, frame: &ShadingFrame, lightv: Vec4, viewv: Vec4) -> Color {
+        let normalv = frame.normalv;
+        let light_dot_normal = lightv.dot(normalv);
+        if light_dot_normal < 0.0 {
+            return Color::BLACK;
+        }
+
+        let rough = oren_nayar(normalv, lightv, viewv, material.diffuse_roughness);
+        let diffuse = color * material.diffuse * (light_dot_normal * rough);
+
+        let reflectv = (-lightv).reflect(normalv);
+        let reflect_dot_eye = reflectv.dot(viewv);
+        let specular = if reflect_dot_eye <= 0.0 {
+            Color::BLACK
+        } else {
+            Color::WHITE * (material.specular * f32::powf(reflect_dot_eye, material.shininess))
+        };
+
+        // phong's diffuse = 1 is a white lambert surface, a brdf of 1 / pi
+        (diffuse + specular) * (1.0 / PI)
+    }
+
+    // the highlight has no cosine at the light, so cosine weighted samples alone would weigh grazing
+    // light by 1 / cos. sampling the cos^shininess lobe around the mirror direction keeps weights bounded
+    fn sample(
+        &self,
+        material: &Material,
+        color: Color,
+        frame: &ShadingFrame,
+        viewv: Vec4,
+        (u1, u2): (f32, f32),
+    ) -> Option<BsdfSample> {
+        let specular_share = Self::specular_share(material);
+        let lightv = if u1 >= specular_share {
+            cosine_hemisphere(frame, ((u1 - specular_share) / (1.0 - specular_share), u2))
+        } else {
+            let u1 = u1 / specular_share;
+            let cos = u1.powf(1.0 / (material.shininess + 1.0));
+            let sin = (1.0 - cos * cos).max(0.0).sqrt();
+            let phi = 2.0 * PI * u2;
+            let mirror = ShadingFrame::from_normal((-viewv).reflect(frame.normalv));
+            mirror.to_world(vector(sin * phi.cos(), sin * phi.sin(), cos))
+        };
+        bsdf_sample(self, material, color, frame, lightv, viewv)
+    }
+
+    fn pdf(&self, material: &Material, frame: &ShadingFrame, lightv: Vec4, viewv: Vec4) -> f32 {
+        let n_dot_l = frame.normalv.dot(lightv);
+        if n_dot_l <= 0.0 {
+            return 0.0;
+        }
+
+        let mirror_dot_l = (-viewv).reflect(frame.normalv).dot(lightv).max(0.0);
+        let specular = (material.shininess + 1.0) / (2.0 * PI) * mirror_dot_l.powf(material.shininess);
+        let specular_share = Self::specular_share(material);
+        (1.0 - specular_share) * n_dot_l / PI + specular_share * specular
+    }
+}
+
+/// GGX cook-torrance (see `cook_torrance`). `Material::color` is the base color,
+/// `diffuse` / `specular` / `shininess` are unused
+#[derive(Debug, Clone, Copy)]
+pub struct Microfacet {
+    pub metallic: f32,
+    pub roughness: f32,
+}
+
+impl Microfacet {
+    /// share of samples spent on the diffuse lobe, the rest follow the GGX normals
+    fn diffuse_share(&self) -> f32 {
+        0.5 * (1.0 - self.metallic)
+    }
+
+    fn alpha(&self) -> f32 {
+        self.roughness.clamp(MIN_ROUGHNESS, 1.0).powi(2)
+    }
+}
+
+impl Bsdf for Microfacet {
+    fn evaluate(&self, _material: &Material, color: Color, frame: &ShadingFrame, lightv: Vec4, viewv: Vec4) -> Color {
+        let n_dot_l = frame.normalv.dot(lightv);
+        cook_torrance(color, self.metallic, self.roughness, frame.normalv, lightv, viewv) * n_dot_l.max(0.0)
+    }
+
+    fn sample(
+        &self,
+        material: &Material,
+        color: Color,
+        frame: &ShadingFrame,
+        viewv: Vec4,
+        (u1, u2): (f32, f32),
+    ) -> Option<BsdfSample> {
+        let diffuse_share = self.diffuse_share();
+        let lightv = if u1 < diffuse_share {
+            cosine_hemisphere(frame, (u1 / diffuse_share, u2))
+        } else {
+            // GGX distribution of normals, then mirror the eye about the normal picked
+            let u1 = (u1 - diffuse_share) / (1.0 - diffuse_share);
+            let a2 = self.alpha() * self.alpha();
+            let cos = ((1.0 - u1) / (1.0 + (a2 - 1.0) * u1)).sqrt();
+            let sin = (1.0 - cos * cos).max(0.0).sqrt();
+            let phi = 2.0 * PI * u2;
+            let halfv = frame.to_world(vector(sin * phi.cos(), sin * phi.sin(), cos));
+            halfv * (2.0 * viewv.dot(halfv)) - viewv
+        };
+        bsdf_sample(self, material, color, frame, lightv, viewv)
+    }
+
+    fn pdf(&self, _material: &Material, frame: &ShadingFrame, lightv: Vec4, viewv: Vec4) -> f32 {
+        let n_dot_l = frame.normalv.dot(lightv);
+        if n_dot_l <= 0.0 {
+            return 0.0;
+        }
+
+        let halfv = (lightv + viewv).normalize();
+        let n_dot_h = frame.normalv.dot(halfv).max(0.0);
+        let v_dot_h = viewv.dot(halfv).abs().max(1e-6);
+        let specular = ggx_distribution(n_dot_h, self.alpha()) * n_dot_h / (4.0 * v_dot_h);
+        let diffuse_share = self.diffuse_share();
+        diffuse_share * n_dot_l / PI + (1.0 - diffuse_share) * specular
+    }
+}
+
+/// microfacet with separate roughness along the surface tangent (u) and bitangent (v), for brushed metal.
+/// the highlight stretches along the rougher direction
+#[derive(Debug, Clone, Copy)]
+pub struct AnisotropicMicrofacet {
+    pub metallic: f32,
+    pub roughness_u: f32,
+    pub roughness_v: f32,
+}
+
+impl Bsdf for AnisotropicMicrofacet {
+    fn evaluate(&self, _material: &Material, color: Color, frame: &ShadingFrame, lightv: Vec4, viewv: Vec4) -> Color {
+        let n_dot_l = frame.normalv.dot(lightv);
+        let roughness = (self.roughness_u, self.roughness_v);
+        cook_torrance_anisotropic(color, self.metallic, roughness, frame, lightv, viewv) * n_dot_l.max(0.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::lib_test::assert_almost_eq_f32;
+
+    use super::*;
+
+    fn frame() -> ShadingFrame {
+        ShadingFrame::new(vector(0.0, 1.0, 0.0), vector(1.0, 0.0, 0.0))
+    }
+
+    /// mean sample weight over a grid of uniform numbers: the directional albedo
+    fn sampled_albedo<B: Bsdf>(bsdf: &B, material: &Material, viewv: Vec4) -> Color {
+        let n = 128;
+        let mut sum = Color::BLACK;
+        for i in 0..n {
+            for j in 0..n {
+                let u = ((i as f32 + 0.5) / n as f32, (j as f32 + 0.5) / n as f32);
+                if let Some(sample) = bsdf.sample(material, material.color, &frame(), viewv, u) {
+                    assert_almost_eq_f32(sample.pdf, bsdf.pdf(material, &frame(), sample.lightv, viewv));
+                    sum += sample.weight;
+                }
+            }
+        }
+        sum * (1.0 / (n * n) as f32)
+    }
+
+    /// the same integral with uniform directions
+    fn integrated_albedo<B: Bsdf>(bsdf: &B, material: &Material, viewv: Vec4) -> Color {
+        let (n_theta, n_phi) = (512, 256);
+        let mut sum = Color::BLACK;
+        for i in 0..n_theta {
+            let cos = (i as f32 + 0.5) / n_theta as f32;
+            let sin = (1.0 - cos * cos).sqrt();
+            for j in 0..n_phi {
+                let phi = 2.0 * PI * (j as f32 + 0.5) / n_phi as f32;
+                let lightv = vector(sin * phi.cos(), cos, sin * phi.sin());
+                sum += bsdf.evaluate(material, material.color, &frame(), lightv, viewv);
+            }
+        }
+        sum * (2.0 * PI / (n_theta * n_phi) as f32)
+    }
+
+    #[test]
+    fn phong_diffuse_albedo() {
+        let material = Material {
+            specular: 0.0,
+            ..Default::default()
+        };
+        let albedo = sampled_albedo(&Phong, &material, vector(0.3, 0.9, 0.1).normalize());
+        assert!((albedo.red - material.diffuse).abs() < 1e-3, "{:?}", albedo);
+    }
+
+    #[test]
+    fn phong_sampling_matches_integral() {
+        let material = Material::default();
+        for viewv in [vector(0.5, 0.8, 0.0), vector(0.99, 0.1, 0.0)] {
+            let viewv = viewv.normalize();
+            let sampled = sampled_albedo(&Phong, &material, viewv);
+            let integrated = integrated_albedo(&Phong, &material, viewv);
+            assert!(
+                (sampled.red - integrated.red).abs() < 0.02,
+                "{:?} vs {:?}",
+                sampled,
+                integrated
+            );
+        }
+    }
+
+    #[test]
+    fn phong_highlight_weights_stay_bounded() {
+        let material = Material::default();
+        // eye just above the horizon: its mirror direction is a grazing light
+        let viewv = vector(0.995, 0.1, 0.0).normalize();
+        for i in 0..64 {
+            for j in 0..64 {
+                let u = ((i as f32 + 0.5) / 64.0, (j as f32 + 0.5) / 64.0);
+                if let Some(sample) = Phong.sample(&material, material.color, &frame(), viewv, u) {
+                    assert!(sample.weight.red < 2.0, "{:?}", sample);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn microfacet_sampling_matches_integral() {
+        let material = Material::default();
+        let viewv = vector(0.5, 0.8, 0.0).normalize();
+        for (metallic, roughness) in [(0.0, 0.5), (1.0, 0.3), (1.0, 0.8)] {
+            let bsdf = Microfacet { metallic, roughness };
+            let sampled = sampled_albedo(&bsdf, &material, viewv);
+            let integrated = integrated_albedo(&bsdf, &material, viewv);
+            assert!(
+                (sampled.red - integrated.red).abs() < 0.02,
+                "metallic {} roughness {}: {:?} vs {:?}",
+                metallic,
+                roughness,
+                sampled,
+                integrated
+            );
+        }
+    }
+
+    #[test]
+    fn samples_stay_above_the_surface() {
+        let material = Material::default();
+        let bsdf = AnisotropicMicrofacet {
+            metallic: 1.0,
+            roughness_u: 0.2,
+            roughness_v: 0.6,
+        };
+        for i in 0..16 {
+            let u = (i as f32 / 16.0, 0.37);
+            let sample = bsdf.sample(&material, Color::WHITE, &frame(), vector(0.0, 1.0, 0.0), u);
+            assert!(sample.is_none_or(|s| s.lightv[1] > 0.0 && s.pdf > 0.0));
+        }
+    }
+}
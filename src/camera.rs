@@ -24,14 +24,19 @@ impl Camera {
     }
 
     pub fn get_ray(&self, x: u32, y: u32) -> Ray {
+        self.get_ray_subpixel(x as f32, y as f32)
+    }
+
+    /// ray through a position inside the pixel grid. (x, y) = (1.0, 2.0) is the same as get_ray(1, 2)
+    pub fn get_ray_subpixel(&self, x: f32, y: f32) -> Ray {
         // frustum의 크기를 구한다 (z가 1이라고 가정)
         let tan = f32::tan(self.fov.radian() / 2.0);
         let half_height = tan;
         let half_width = tan * self.aspect;
 
         // -1 to 1
-        let fx = ((x as f32) / ((self.hsize - 1) as f32)) * 2.0 - 1.0;
-        let fy = ((y as f32) / ((self.vsize - 1) as f32)) * -2.0 + 1.0;
+        let fx = (x / ((self.hsize - 1) as f32)) * 2.0 - 1.0;
+        let fy = (y / ((self.vsize - 1) as f32)) * -2.0 + 1.0;
 
         // create ray
         let ray_start = self.inv_transform * point(0.0, 0.0, 0.0);
@@ -10,8 +10,8 @@ pub struct Canvas {
 impl Canvas {
     /// gamma: gamma correction value. pow(color, 1/gamma). 2.2 if None
     pub fn new(width: u32, height: u32, gamma: Option<f32>) -> Self {
-        let buff = vec![Color::new(0.0, 0.0, 0.0); (width * height) as usize];
-        let gamma = if let Some(gamma) = gamma { gamma } else { 2.2 };
+        let buff = vec![Color::new(0.0, 0.0, 0.0); width as usize * height as usize];
+        let gamma = gamma.unwrap_or(2.2);
         Self {
             width,
             height,
@@ -21,13 +21,12 @@ impl Canvas {
     }
 
     pub fn pixel_at(&self, x: u32, y: u32) -> Color {
-        let idx = self.get_index(x, y);
-        self.buff[idx as usize]
+        self.buff[self.get_index(x, y)]
     }
 
     pub fn write_pixel(&mut self, x: u32, y: u32, color: Color) {
         let idx = self.get_index(x, y);
-        self.buff[idx as usize] = color
+        self.buff[idx] = color
     }
 
     pub fn get_size(&self) -> (u32, u32) {
@@ -38,7 +37,7 @@ impl Canvas {
         let mut image = image::ImageBuffer::new(self.width, self.height);
 
         for (idx, color) in self.buff.iter().enumerate() {
-            let (x, y) = Self::get_xy(self.width, idx as u32);
+            let (x, y) = Self::get_xy(self.width, idx);
 
             let color = image::Rgb([
                 (f32::powf(color.red.clamp(0.0, 1.0), 1. / self.gamma) * 255.0 + 0.5) as u8,
@@ -52,15 +51,15 @@ impl Canvas {
         image.save(path).unwrap();
     }
 
-    fn get_index(&self, x: u32, y: u32) -> u32 {
-        x + y * self.width
+    fn get_index(&self, x: u32, y: u32) -> usize {
+        x as usize + y as usize * self.width as usize
     }
 
-    fn get_xy(width: u32, idx: u32) -> (u32, u32) {
-        let x = idx % width;
-        let y = idx / width;
+    fn get_xy(width: u32, idx: usize) -> (u32, u32) {
+        let x = idx % width as usize;
+        let y = idx / width as usize;
 
-        (x, y)
+        (x as u32, y as u32)
     }
 }
 
@@ -27,6 +27,11 @@ impl Color {
             blue: b,
         }
     }
+
+    /// relative luminance (rec. 709 weights)
+    pub fn luminance(&self) -> f32 {
+        0.2126 * self.red + 0.7152 * self.green + 0.0722 * self.blue
+    }
 }
 
 impl Add for Color {
@@ -77,7 +82,7 @@ impl AddAssign for Color {
 
 #[cfg(test)]
 mod tests {
-    use crate::assert_almost_eq_color;
+    use crate::{assert_almost_eq_color, assert_almost_eq_f32};
 
     use super::*;
 
@@ -124,4 +129,10 @@ mod tests {
         c1 += c2;
         assert_almost_eq_color(c1, Color::new(0.5, 0.7, 0.9));
     }
+
+    #[test]
+    fn luminance() {
+        assert_almost_eq_f32(Color::WHITE.luminance(), 1.0);
+        assert_almost_eq_f32(Color::new(0.0, 1.0, 0.0).luminance(), 0.7152);
+    }
 }
@@ -0,0 +1,573 @@
+//! coordinator / worker rendering over tcp.
+//!
+//! the coordinator connects to every worker, sends the scene text once, then hands out tiles
+//! one at a time. a worker that fails (connection dropped, timeout) has its tile put back in
+//! the queue for the other workers, and is reconnected up to `max_retries` times, waiting a little
+//! longer before each attempt.
+//!
+//! messages (little endian):
+//! - coordinator -> worker: `MSG_SCENE len:u32 utf8`, `MSG_TILE x:u32 y:u32 w:u32 h:u32`
+//! - worker -> coordinator: scene reply `0` (ok) or `1 len:u32 utf8` (error),
+//!   tile reply `0 w*h rgb f32` or `1 len:u32 utf8` (tile outside the image)
+//!
+//! strings longer than `MAX_STRING_BYTES` are refused.
+
+use std::collections::VecDeque;
+use std::convert::TryFrom;
+use std::io::{self, BufReader, BufWriter, Read, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream};
+use std::num::NonZeroU32;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{mpsc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+use crate::{parse_scene, render_pixel, Camera, Canvas, Color, World};
+
+const MSG_SCENE: u8 = 1;
+const MSG_TILE: u8 = 2;
+
+const REPLY_OK: u8 = 0;
+const REPLY_ERROR: u8 = 1;
+
+/// longest scene (or error message) accepted off the wire, so a bad length cannot exhaust memory
+pub const MAX_STRING_BYTES: u32 = 16 * 1024 * 1024;
+
+/// a worker drops a coordinator that sends nothing for this long
+pub const SESSION_TIMEOUT: Duration = Duration::from_secs(600);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Tile {
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+}
+
+impl Tile {
+    /// whether the tile lies inside a width x height image
+    pub fn fits(&self, width: u32, height: u32) -> bool {
+        let right = self.x.checked_add(self.width);
+        let bottom = self.y.checked_add(self.height);
+        right.is_some_and(|right| right <= width) && bottom.is_some_and(|bottom| bottom <= height)
+    }
+}
+
+/// splits a width x height image into tiles of at most tile_size x tile_size
+pub fn split_tiles(width: u32, height: u32, tile_size: NonZeroU32) -> Vec<Tile> {
+    let tile_size = tile_size.get();
+    let mut tiles = Vec::new();
+    for y in (0..height).step_by(tile_size as usize) {
+        for x in (0..width).step_by(tile_size as usize) {
+            tiles.push(Tile {
+                x,
+                y,
+                width: tile_size.min(width - x),
+                height: tile_size.min(height - y),
+            });
+        }
+    }
+    tiles
+}
+
+/// serves coordinators forever, each connection on its own thread
+pub fn run_worker(listener: TcpListener) -> io::Result<()> {
+    for stream in listener.incoming() {
+        let stream = stream?;
+        // a broken or silent session must not take the worker down or hold up the others
+        thread::spawn(move || serve_coordinator(stream));
+    }
+    Ok(())
+}
+
+/// handles one coordinator session until it disconnects, or sends nothing for `SESSION_TIMEOUT`
+pub fn serve_coordinator(stream: TcpStream) -> io::Result<()> {
+    stream.set_read_timeout(Some(SESSION_TIMEOUT))?;
+    let mut reader = BufReader::new(stream.try_clone()?);
+    let mut writer = BufWriter::new(stream);
+    let mut scene: Option<(World, Camera)> = None;
+
+    loop {
+        let msg = match read_u8(&mut reader) {
+            Ok(msg) => msg,
+            Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => return Ok(()),
+            Err(e) => return Err(e),
+        };
+
+        match msg {
+            MSG_SCENE => {
+                let src = read_string(&mut reader)?;
+                match parse_scene(&src) {
+                    Ok(parsed) => {
+                        scene = Some(parsed);
+                        write_u8(&mut writer, REPLY_OK)?;
+                    }
+                    Err(e) => {
+                        write_u8(&mut writer, REPLY_ERROR)?;
+                        write_string(&mut writer, &e.to_string())?;
+                    }
+                }
+            }
+            MSG_TILE => {
+                let tile = read_tile(&mut reader)?;
+                let (world, camera) = scene
+                    .as_ref()
+                    .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "tile before scene"))?;
+
+                if !tile.fits(camera.hsize, camera.vsize) {
+                    write_u8(&mut writer, REPLY_ERROR)?;
+                    write_string(&mut writer, &format!("tile {:?} is outside the image", tile))?;
+                    writer.flush()?;
+                    continue;
+                }
+
+                let (xs, ys) = (tile.x..tile.x + tile.width, tile.y..tile.y + tile.height);
+                let mut colors = Vec::with_capacity((tile.width * tile.height) as usize);
+                for y in ys.clone() {
+                    for x in xs.clone() {
+                        colors.push(render_pixel(world, camera, x, y));
+                    }
+                }
+                if let Some(outline) = &world.outline {
+                    for (x, y) in outline.edges(world, camera, xs, ys) {
+                        colors[((x - tile.x) + (y - tile.y) * tile.width) as usize] = outline.color;
+                    }
+                }
+
+                write_u8(&mut writer, REPLY_OK)?;
+                for color in colors {
+                    write_color(&mut writer, color)?;
+                }
+            }
+            _ => return Err(io::Error::new(io::ErrorKind::InvalidData, "unknown message")),
+        }
+
+        writer.flush()?;
+    }
+}
+
+pub struct Coordinator {
+    pub workers: Vec<SocketAddr>,
+    pub tile_size: NonZeroU32,
+    /// reconnect attempts per worker before it is given up
+    pub max_retries: u32,
+    /// wait before the first reconnect, doubled for each one after it (up to 1024 times)
+    pub retry_delay: Duration,
+    /// a worker that does not answer within this time is treated as dead
+    pub timeout: Option<Duration>,
+}
+
+impl Coordinator {
+    pub fn new(workers: Vec<SocketAddr>) -> Self {
+        Self {
+            workers,
+            tile_size: NonZeroU32::new(32).unwrap(),
+            max_retries: 2,
+            retry_delay: Duration::from_millis(100),
+            timeout: Some(Duration::from_secs(120)),
+        }
+    }
+
+    /// renders `scene` (see `parse_scene`) on the workers and assembles the tiles.
+    /// fails only when the scene is invalid or every worker has died
+    pub fn render(&self, scene: &str, gamma: Option<f32>) -> io::Result<Canvas> {
+        check_string_len(scene)?;
+        let (_, camera) = parse_scene(scene).map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+
+        let tiles = split_tiles(camera.hsize, camera.vsize, self.tile_size);
+        let queue = Mutex::new(tiles.iter().copied().collect::<VecDeque<_>>());
+        let remaining = AtomicUsize::new(tiles.len());
+        let (tx, rx) = mpsc::channel();
+
+        let mut canvas = Canvas::new(camera.hsize, camera.vsize, gamma);
+
+        thread::scope(|s| {
+            for addr in &self.workers {
+                let tx = tx.clone();
+                let queue = &queue;
+                let remaining = &remaining;
+                s.spawn(move || self.drive_worker(*addr, scene, queue, remaining, tx));
+            }
+            drop(tx);
+
+            // ends when all tiles arrived, or when every worker thread has given up
+            for (tile, colors) in rx.iter().take(tiles.len()) {
+                write_tile(&mut canvas, tile, &colors);
+            }
+        });
+
+        if remaining.load(Ordering::SeqCst) > 0 {
+            return Err(io::Error::other("all workers failed"));
+        }
+
+        Ok(canvas)
+    }
+
+    fn drive_worker(
+        &self,
+        addr: SocketAddr,
+        scene: &str,
+        queue: &Mutex<VecDeque<Tile>>,
+        remaining: &AtomicUsize,
+        tx: mpsc::Sender<(Tile, Vec<Color>)>,
+    ) {
+        let mut failures = 0;
+
+        while remaining.load(Ordering::SeqCst) > 0 && failures <= self.max_retries {
+            if failures > 0 {
+                thread::sleep(self.retry_delay * 2u32.pow((failures - 1).min(10)));
+            }
+
+            let mut conn = match self.connect(addr, scene) {
+                Ok(conn) => conn,
+                Err(_) => {
+                    failures += 1;
+                    continue;
+                }
+            };
+
+            while remaining.load(Ordering::SeqCst) > 0 {
+                let tile = queue.lock().unwrap().pop_front();
+                let tile = match tile {
+                    Some(tile) => tile,
+                    None => {
+                        // other workers still own the last tiles; wait in case one of them dies
+                        thread::sleep(Duration::from_millis(10));
+                        continue;
+                    }
+                };
+
+                match conn.render_tile(tile) {
+                    Ok(colors) => {
+                        remaining.fetch_sub(1, Ordering::SeqCst);
+                        let _ = tx.send((tile, colors));
+                    }
+                    Err(_) => {
+                        queue.lock().unwrap().push_back(tile);
+                        failures += 1;
+                        break;
+                    }
+                }
+            }
+        }
+    }
+
+    fn connect(&self, addr: SocketAddr, scene: &str) -> io::Result<WorkerConnection> {
+        let stream = match self.timeout {
+            Some(timeout) => TcpStream::connect_timeout(&addr, timeout)?,
+            None => TcpStream::connect(addr)?,
+        };
+        stream.set_read_timeout(self.timeout)?;
+        stream.set_nodelay(true)?;
+
+        let mut conn = WorkerConnection {
+            reader: BufReader::new(stream.try_clone()?),
+            writer: BufWriter::new(stream),
+        };
+
+        write_u8(&mut conn.writer, MSG_SCENE)?;
+        write_string(&mut conn.writer, scene)?;
+        conn.writer.flush()?;
+
+        match read_u8(&mut conn.reader)? {
+            REPLY_OK => Ok(conn),
+            _ => {
+                let message = read_string(&mut conn.reader)?;
+                Err(io::Error::new(io::ErrorKind::InvalidData, message))
+            }
+        }
+    }
+}
+
+struct WorkerConnection {
+    reader: BufReader<TcpStream>,
+    writer: BufWriter<TcpStream>,
+}
+
+impl WorkerConnection {
+    fn render_tile(&mut self, tile: Tile) -> io::Result<Vec<Color>> {
+        write_u8(&mut self.writer, MSG_TILE)?;
+        write_tile_header(&mut self.writer, tile)?;
+        self.writer.flush()?;
+
+        if read_u8(&mut self.reader)? != REPLY_OK {
+            let message = read_string(&mut self.reader)?;
+            return Err(io::Error::new(io::ErrorKind::InvalidData, message));
+        }
+
+        (0..tile.width * tile.height)
+            .map(|_| read_color(&mut self.reader))
+            .collect()
+    }
+}
+
+fn write_tile(canvas: &mut Canvas, tile: Tile, colors: &[Color]) {
+    for ty in 0..tile.height {
+        for tx in 0..tile.width {
+            let color = colors[(tx + ty * tile.width) as usize];
+            canvas.write_pixel(tile.x + tx, tile.y + ty, color);
+        }
+    }
+}
+
+fn read_u8(r: &mut impl Read) -> io::Result<u8> {
+    let mut buf = [0; 1];
+    r.read_exact(&mut buf)?;
+    Ok(buf[0])
+}
+
+fn read_u32(r: &mut impl Read) -> io::Result<u32> {
+    let mut buf = [0; 4];
+    r.read_exact(&mut buf)?;
+    Ok(u32::from_le_bytes(buf))
+}
+
+fn read_f32(r: &mut impl Read) -> io::Result<f32> {
+    let mut buf = [0; 4];
+    r.read_exact(&mut buf)?;
+    Ok(f32::from_le_bytes(buf))
+}
+
+fn read_string(r: &mut impl Read) -> io::Result<String> {
+    let len = read_u32(r)?;
+    if len > MAX_STRING_BYTES {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("string of {} bytes is too long", len),
+        ));
+    }
+    let mut buf = vec![0; len as usize];
+    r.read_exact(&mut buf)?;
+    String::from_utf8(buf).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+}
+
+fn read_tile(r: &mut impl Read) -> io::Result<Tile> {
+    Ok(Tile {
+        x: read_u32(r)?,
+        y: read_u32(r)?,
+        width: read_u32(r)?,
+        height: read_u32(r)?,
+    })
+}
+
+fn read_color(r: &mut impl Read) -> io::Result<Color> {
+    Ok(Color::new(read_f32(r)?, read_f32(r)?, read_f32(r)?))
+}
+
+fn write_u8(w: &mut impl Write, v: u8) -> io::Result<()> {
+    w.write_all(&[v])
+}
+
+fn write_u32(w: &mut impl Write, v: u32) -> io::Result<()> {
+    w.write_all(&v.to_le_bytes())
+}
+
+fn write_string(w: &mut impl Write, s: &str) -> io::Result<()> {
+    write_u32(w, check_string_len(s)?)?;
+    w.write_all(s.as_bytes())
+}
+
+/// length of `s` as sent on the wire. fails for strings the other side would refuse
+fn check_string_len(s: &str) -> io::Result<u32> {
+    match u32::try_from(s.len()) {
+        Ok(len) if len <= MAX_STRING_BYTES => Ok(len),
+        _ => Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!(
+                "{} bytes is longer than the {} a worker accepts",
+                s.len(),
+                MAX_STRING_BYTES
+            ),
+        )),
+    }
+}
+
+fn write_tile_header(w: &mut impl Write, tile: Tile) -> io::Result<()> {
+    write_u32(w, tile.x)?;
+    write_u32(w, tile.y)?;
+    write_u32(w, tile.width)?;
+    write_u32(w, tile.height)
+}
+
+fn write_color(w: &mut impl Write, c: Color) -> io::Result<()> {
+    w.write_all(&c.red.to_le_bytes())?;
+    w.write_all(&c.green.to_le_bytes())?;
+    w.write_all(&c.blue.to_le_bytes())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{lib_test::assert_almost_eq_color, render};
+
+    use super::*;
+
+    const SCENE: &str = "
+        camera 40 30 50  -3 2 5.5  2 0 12  0 1 0
+        light -5 5 5  0.9 0.9 0.9
+        sphere -0.5 1 10  1  color=0.8,0.4,0.2 ambient=0.2 diffuse=0.8
+        aabb -4 0 -25  4 100 13  inside  color=0.3,0.3,0.3 pattern=check
+    ";
+
+    fn spawn_worker() -> SocketAddr {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        thread::spawn(move || run_worker(listener));
+        addr
+    }
+
+    /// accepts connections, reads the scene, then hangs up on the first tile
+    fn spawn_broken_worker() -> SocketAddr {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        thread::spawn(move || {
+            for stream in listener.incoming() {
+                let mut stream = stream.unwrap();
+                let _ = read_u8(&mut stream);
+                let _ = read_string(&mut stream);
+                let _ = write_u8(&mut stream, REPLY_OK);
+                let _ = read_u8(&mut stream);
+            }
+        });
+        addr
+    }
+
+    fn assert_same_as_local(canvas: &Canvas, scene: &str) {
+        let (world, camera) = parse_scene(scene).unwrap();
+        let mut expected = Canvas::new(camera.hsize, camera.vsize, None);
+        render(&world, &camera, &mut expected);
+
+        for y in 0..camera.vsize {
+            for x in 0..camera.hsize {
+                assert_almost_eq_color(canvas.pixel_at(x, y), expected.pixel_at(x, y));
+            }
+        }
+    }
+
+    #[test]
+    fn split_into_tiles() {
+        let tiles = split_tiles(10, 5, NonZeroU32::new(4).unwrap());
+        assert_eq!(tiles.len(), 6);
+        assert_eq!(
+            tiles[2],
+            Tile {
+                x: 8,
+                y: 0,
+                width: 2,
+                height: 4
+            }
+        );
+        let pixels: u32 = tiles.iter().map(|t| t.width * t.height).sum();
+        assert_eq!(pixels, 50);
+    }
+
+    #[test]
+    fn render_with_several_workers() {
+        let workers = vec![spawn_worker(), spawn_worker(), spawn_worker()];
+        let mut coordinator = Coordinator::new(workers);
+        coordinator.tile_size = NonZeroU32::new(8).unwrap();
+
+        let canvas = coordinator.render(SCENE, None).unwrap();
+        assert_same_as_local(&canvas, SCENE);
+    }
+
+    #[test]
+    fn workers_draw_the_outline_per_tile() {
+        let scene = format!("{}\noutline", SCENE);
+        let mut coordinator = Coordinator::new(vec![spawn_worker(), spawn_worker()]);
+        coordinator.tile_size = NonZeroU32::new(8).unwrap();
+
+        let canvas = coordinator.render(&scene, None).unwrap();
+        assert_same_as_local(&canvas, &scene);
+    }
+
+    #[test]
+    fn dead_worker_tiles_are_retried() {
+        let workers = vec![spawn_broken_worker(), spawn_worker()];
+        let mut coordinator = Coordinator::new(workers);
+        coordinator.tile_size = NonZeroU32::new(8).unwrap();
+
+        let canvas = coordinator.render(SCENE, None).unwrap();
+        assert_same_as_local(&canvas, SCENE);
+    }
+
+    #[test]
+    fn fails_when_every_worker_dies() {
+        let coordinator = Coordinator::new(vec![spawn_broken_worker()]);
+        assert!(coordinator.render(SCENE, None).is_err());
+    }
+
+    #[test]
+    fn worker_refuses_tiles_outside_the_image() {
+        let stream = TcpStream::connect(spawn_worker()).unwrap();
+        let mut reader = BufReader::new(stream.try_clone().unwrap());
+        let mut writer = BufWriter::new(stream);
+        write_u8(&mut writer, MSG_SCENE).unwrap();
+        write_string(&mut writer, SCENE).unwrap();
+        writer.flush().unwrap();
+        assert_eq!(read_u8(&mut reader).unwrap(), REPLY_OK);
+
+        for (x, width) in [(38, 8), (u32::MAX, 2)] {
+            write_u8(&mut writer, MSG_TILE).unwrap();
+            write_tile_header(
+                &mut writer,
+                Tile {
+                    x,
+                    y: 0,
+                    width,
+                    height: 1,
+                },
+            )
+            .unwrap();
+            writer.flush().unwrap();
+            assert_eq!(read_u8(&mut reader).unwrap(), REPLY_ERROR);
+            assert!(read_string(&mut reader).unwrap().contains("outside the image"));
+        }
+
+        // the session goes on
+        write_u8(&mut writer, MSG_TILE).unwrap();
+        write_tile_header(
+            &mut writer,
+            Tile {
+                x: 38,
+                y: 0,
+                width: 2,
+                height: 1,
+            },
+        )
+        .unwrap();
+        writer.flush().unwrap();
+        assert_eq!(read_u8(&mut reader).unwrap(), REPLY_OK);
+    }
+
+    #[test]
+    fn oversized_string_is_refused() {
+        let mut bytes = Vec::new();
+        write_u32(&mut bytes, MAX_STRING_BYTES + 1).unwrap();
+        let err = read_string(&mut bytes.as_slice()).err().unwrap();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn oversized_scene_is_refused_before_sending() {
+        let coordinator = Coordinator::new(vec![spawn_worker()]);
+        let scene = " ".repeat(MAX_STRING_BYTES as usize + 1);
+        let err = coordinator.render(&scene, None).err().unwrap();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+    }
+
+    #[test]
+    fn silent_coordinator_does_not_block_the_worker() {
+        let addr = spawn_worker();
+        let _silent = TcpStream::connect(addr).unwrap();
+
+        let canvas = Coordinator::new(vec![addr]).render(SCENE, None).unwrap();
+        assert_same_as_local(&canvas, SCENE);
+    }
+
+    #[test]
+    fn invalid_scene_is_rejected() {
+        let coordinator = Coordinator::new(vec![spawn_worker()]);
+        let err = coordinator.render("sphere 0 0 0 1", None).err().unwrap();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+    }
+}
@@ -0,0 +1,77 @@
+use crate::{vector, Vec4};
+
+/// orthonormal frame at a shading point: the normal plus the surface's u (tangent) and v (bitangent)
+/// directions. anisotropic and tangent space effects are defined relative to it
+#[derive(Debug, Clone, Copy)]
+pub struct ShadingFrame {
+    pub normalv: Vec4,
+    pub tangent: Vec4,
+    pub bitangent: Vec4,
+}
+
+impl ShadingFrame {
+    /// `tangent` is made orthogonal to `normalv`. when it is (nearly) parallel to the normal,
+    /// as at the poles of a sphere, a fixed axis is used instead so the frame never degenerates
+    pub fn new(normalv: Vec4, tangent: Vec4) -> Self {
+        let mut t = tangent - normalv * tangent.dot(normalv);
+        if t.mag() < 1e-4 {
+            let axis = if normalv[0].abs() < 0.9 {
+                vector(1.0, 0.0, 0.0)
+            } else {
+                vector(0.0, 1.0, 0.0)
+            };
+            t = axis - normalv * axis.dot(normalv);
+        }
+        let tangent = t.normalize();
+
+        Self {
+            normalv,
+            tangent,
+            bitangent: tangent.cross(normalv),
+        }
+    }
+
+    /// for isotropic shading where the tangent does not matter
+    pub fn from_normal(normalv: Vec4) -> Self {
+        Self::new(normalv, vector(1.0, 0.0, 0.0))
+    }
+
+    /// world direction -> (tangent, bitangent, normal) coordinates
+    pub fn to_local(&self, v: Vec4) -> Vec4 {
+        vector(v.dot(self.tangent), v.dot(self.bitangent), v.dot(self.normalv))
+    }
+
+    pub fn to_world(&self, v: Vec4) -> Vec4 {
+        self.tangent * v[0] + self.bitangent * v[1] + self.normalv * v[2]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::lib_test::assert_almost_eq_tuple;
+
+    use super::*;
+
+    #[test]
+    fn frame_follows_uv() {
+        // front (-z) face of a box: u along +x, v along +y
+        let frame = ShadingFrame::new(vector(0.0, 0.0, -1.0), vector(1.0, 0.0, 0.0));
+        assert_almost_eq_tuple(frame.tangent, vector(1.0, 0.0, 0.0));
+        assert_almost_eq_tuple(frame.bitangent, vector(0.0, 1.0, 0.0));
+
+        let v = vector(0.3, -0.4, 0.5);
+        assert_almost_eq_tuple(frame.to_local(v), vector(0.3, -0.4, -0.5));
+        assert_almost_eq_tuple(frame.to_world(frame.to_local(v)), v);
+    }
+
+    #[test]
+    fn degenerate_tangent() {
+        let normalv = vector(1.0, 0.0, 0.0);
+        let frame = ShadingFrame::new(normalv, vector(0.0, 0.0, 0.0));
+        assert!(frame.tangent.dot(normalv).abs() < 1e-5 && frame.bitangent.dot(normalv).abs() < 1e-5);
+        assert!((frame.tangent.mag() - 1.0).abs() < 1e-5);
+
+        let frame = ShadingFrame::new(normalv, normalv);
+        assert!(frame.tangent.dot(normalv).abs() < 1e-5);
+    }
+}
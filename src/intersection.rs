@@ -1,4 +1,4 @@
-use crate::{Material, Vec4};
+use crate::{vector, Material, Uv, Vec4};
 
 #[derive(Copy, Clone, Debug)]
 pub struct Intersection<'a> {
@@ -6,6 +6,12 @@ pub struct Intersection<'a> {
     pub pos: Vec4,
     pub normalv: Vec4,
     pub material: &'a Material,
+    /// `pos` in the object's own space, where patterns are evaluated. same as `pos` unless the object sets it
+    pub object_pos: Vec4,
+    /// surface coordinates for textures. (0, 0) unless the object sets it
+    pub uv: Uv,
+    /// direction of increasing u on the surface. (1, 0, 0) unless the object sets it
+    pub tangent: Vec4,
 }
 
 impl<'a> Intersection<'a> {
@@ -15,23 +21,86 @@ impl<'a> Intersection<'a> {
             pos,
             normalv,
             material,
+            object_pos: pos,
+            uv: (0.0, 0.0),
+            tangent: vector(1.0, 0.0, 0.0),
         }
     }
 }
 
 pub fn get_frontmost_intersection<'a>(mut intersections: Vec<Intersection<'a>>) -> Option<Intersection<'a>> {
     intersections.sort_by(|a, b| a.t.partial_cmp(&b.t).unwrap());
-    let first = intersections.iter().filter(|a| a.t > 0.0).next();
+    let first = intersections.iter().find(|a| a.t > 0.0);
 
-    first.map(|i| *i)
+    first.copied()
+}
+
+/// refractive index on the ray side (n1) and the far side (n2) of `intersections[hit]`.
+/// walks the sorted list keeping track of which objects the ray is inside, so nested media work.
+/// objects are told apart by their material. `wavelength` (nm) selects the index of dispersive materials
+pub fn refractive_indices(intersections: &[Intersection], hit: usize, wavelength: Option<f32>) -> (f32, f32) {
+    let mut containers: Vec<&Material> = Vec::new();
+    let mut n1 = 1.0;
+
+    for (idx, i) in intersections.iter().enumerate() {
+        if idx == hit {
+            n1 = containers
+                .last()
+                .map(|m| m.refractive_index_at(wavelength))
+                .unwrap_or(1.0);
+        }
+
+        match containers.iter().position(|m| std::ptr::eq(*m, i.material)) {
+            Some(pos) => {
+                containers.remove(pos);
+            }
+            None => containers.push(i.material),
+        }
+
+        if idx == hit {
+            let n2 = containers
+                .last()
+                .map(|m| m.refractive_index_at(wavelength))
+                .unwrap_or(1.0);
+            return (n1, n2);
+        }
+    }
+
+    (n1, 1.0)
+}
+
+/// schlick's approximation of the fresnel reflectance.
+/// `dir`: incoming ray direction, `normalv`: normal facing against `dir`
+pub fn schlick(dir: Vec4, normalv: Vec4, n1: f32, n2: f32) -> f32 {
+    let mut cos = -dir.dot(normalv);
+
+    if n1 > n2 {
+        let n = n1 / n2;
+        let sin2_t = n * n * (1.0 - cos * cos);
+        if sin2_t > 1.0 {
+            // total internal reflection
+            return 1.0;
+        }
+        cos = (1.0 - sin2_t).sqrt();
+    }
+
+    let r0 = ((n1 - n2) / (n1 + n2)).powi(2);
+    r0 + (1.0 - r0) * (1.0 - cos).powi(5)
 }
 
 #[cfg(test)]
 mod test {
-    use crate::{point, vector};
+    use crate::{lib_test::assert_almost_eq_f32, point, vector, Object, Ray, Sphere};
 
     use super::*;
 
+    fn glass_sphere(center: Vec4, r: f32, refractive_index: f32) -> Sphere {
+        let mut sphere = Sphere::new(center, r);
+        sphere.mat.transparency = 1.0;
+        sphere.mat.refractive_index = refractive_index;
+        sphere
+    }
+
     #[test]
     fn front_most() {
         let mat = Material::default();
@@ -43,4 +112,44 @@ mod test {
         let front_most = get_frontmost_intersection(intersections).unwrap();
         assert_eq!(front_most.t, 1.0);
     }
+
+    #[test]
+    fn n1_n2_at_various_intersections() {
+        let a = glass_sphere(point(0.0, 0.0, 0.0), 2.0, 1.5);
+        let b = glass_sphere(point(0.0, 0.0, -0.25), 1.0, 2.0);
+        let c = glass_sphere(point(0.0, 0.0, 0.25), 1.0, 2.5);
+
+        let ray = Ray::new(point(0.0, 0.0, -4.0), vector(0.0, 0.0, 1.0));
+        let mut xs = Vec::new();
+        xs.append(&mut a.ray_intersect(&ray));
+        xs.append(&mut b.ray_intersect(&ray));
+        xs.append(&mut c.ray_intersect(&ray));
+        xs.sort_by(|a, b| a.t.partial_cmp(&b.t).unwrap());
+
+        let expected = [(1.0, 1.5), (1.5, 2.0), (2.0, 2.5), (2.5, 2.5), (2.5, 1.5), (1.5, 1.0)];
+        for (idx, (n1, n2)) in expected.iter().enumerate() {
+            assert_eq!(refractive_indices(&xs, idx, None), (*n1, *n2), "intersection {}", idx);
+        }
+    }
+
+    #[test]
+    fn schlick_under_total_internal_reflection() {
+        let inside = f32::sqrt(2.0) / 2.0;
+        let reflectance = schlick(vector(0.0, 1.0, 0.0), vector(0.0, -inside, -inside), 1.5, 1.0);
+        assert_almost_eq_f32(reflectance, 1.0);
+    }
+
+    #[test]
+    fn schlick_perpendicular() {
+        let reflectance = schlick(vector(0.0, 1.0, 0.0), vector(0.0, -1.0, 0.0), 1.5, 1.0);
+        assert_almost_eq_f32(reflectance, 0.04);
+    }
+
+    #[test]
+    fn schlick_small_angle_n2_greater() {
+        // ray at y = 0.99 grazing a unit glass sphere
+        let normalv = vector(0.0, 0.99, -f32::sqrt(1.0 - 0.99 * 0.99));
+        let reflectance = schlick(vector(0.0, 0.0, 1.0), normalv, 1.0, 1.5);
+        assert!((reflectance - 0.48873).abs() < 0.001, "{}", reflectance);
+    }
 }
@@ -0,0 +1,114 @@
+use std::f32::consts::PI;
+
+use crate::{ggx_distribution, schlick, smith_g1, Color, Vec4, MIN_ROUGHNESS};
+
+/// clear dielectric layer (lacquer, varnish) on top of a material's own shading model.
+/// light reflected by the coat's fresnel never reaches the base; the rest is shaded by the
+/// base and has to get through the coat again on the way out
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ClearCoat {
+    /// 0 = no coat, 1 = full coat
+    pub weight: f32,
+    pub roughness: f32,
+    /// 1.5 for lacquer / varnish
+    pub refractive_index: f32,
+}
+
+impl Default for ClearCoat {
+    fn default() -> Self {
+        Self {
+            weight: 1.0,
+            roughness: 0.05,
+            refractive_index: 1.5,
+        }
+    }
+}
+
+impl ClearCoat {
+    /// share of light the coat reflects, for light arriving from / leaving toward `dir`
+    pub fn fresnel(&self, dir: Vec4, normalv: Vec4) -> f32 {
+        self.weight * schlick(-dir, normalv, 1.0, self.refractive_index)
+    }
+
+    /// GGX specular brdf of the coat alone. colorless
+    pub fn specular(&self, normalv: Vec4, lightv: Vec4, viewv: Vec4) -> f32 {
+        let n_dot_l = normalv.dot(lightv);
+        let n_dot_v = normalv.dot(viewv);
+        if n_dot_l <= 0.0 || n_dot_v <= 0.0 {
+            return 0.0;
+        }
+
+        let alpha = self.roughness.clamp(MIN_ROUGHNESS, 1.0).powi(2);
+        let halfv = (lightv + viewv).normalize();
+        let d = ggx_distribution(normalv.dot(halfv).max(0.0), alpha);
+        let g = smith_g1(n_dot_l, alpha) * smith_g1(n_dot_v, alpha);
+        self.fresnel(viewv, halfv) * d * g / (4.0 * n_dot_l * n_dot_v)
+    }
+
+    /// `base`: light the base layer sends toward the eye (what `point_lighting` returns without a coat).
+    /// adds the coat's highlight and removes what the coat kept from the base
+    pub fn layer(&self, base: Color, intensity: Color, normalv: Vec4, lightv: Vec4, viewv: Vec4) -> Color {
+        let transmitted = (1.0 - self.fresnel(lightv, normalv)) * (1.0 - self.fresnel(viewv, normalv));
+        let n_dot_l = normalv.dot(lightv).max(0.0);
+        // scaled by pi like the microfacet models in point_lighting
+        base * transmitted + intensity * (self.specular(normalv, lightv, viewv) * PI * n_dot_l)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{lib_test::assert_almost_eq_color, vector};
+
+    use super::*;
+
+    #[test]
+    fn zero_weight_is_transparent() {
+        let coat = ClearCoat {
+            weight: 0.0,
+            ..Default::default()
+        };
+        let normalv = vector(0.0, 1.0, 0.0);
+        let lightv = vector(0.0, 1.0, 0.0);
+        let base = Color::new(0.3, 0.5, 0.7);
+        assert_almost_eq_color(coat.layer(base, Color::WHITE, normalv, lightv, lightv), base);
+    }
+
+    #[test]
+    fn coat_dims_base_at_grazing_angles() {
+        let coat = ClearCoat::default();
+        let normalv = vector(0.0, 1.0, 0.0);
+        let lightv = vector(0.0, 1.0, 0.0);
+        let base = Color::WHITE;
+        let head_on = coat.layer(base, Color::BLACK, normalv, lightv, vector(0.0, 1.0, 0.0));
+        let grazing = coat.layer(base, Color::BLACK, normalv, lightv, vector(1.0, 0.05, 0.0).normalize());
+        // 4% reflected both ways at normal incidence
+        assert!((head_on.red - 0.96 * 0.96).abs() < 1e-3);
+        assert!(grazing.red < 0.5);
+    }
+
+    #[test]
+    fn coat_over_white_lambert_conserves_energy() {
+        let coat = ClearCoat {
+            roughness: 0.4,
+            ..Default::default()
+        };
+        let normalv = vector(0.0, 1.0, 0.0);
+        for viewv in [vector(0.0, 1.0, 0.0), vector(0.8, 0.3, 0.0).normalize()] {
+            let (n_theta, n_phi) = (256, 128);
+            let mut albedo = 0.0;
+            for i in 0..n_theta {
+                let cos = (i as f32 + 0.5) / n_theta as f32;
+                let sin = (1.0 - cos * cos).sqrt();
+                for j in 0..n_phi {
+                    let phi = 2.0 * PI * (j as f32 + 0.5) / n_phi as f32;
+                    let lightv = vector(sin * phi.cos(), cos, sin * phi.sin());
+                    // white lambert in point_lighting units is just n.l
+                    let base = Color::WHITE * cos;
+                    albedo += coat.layer(base, Color::WHITE, normalv, lightv, viewv).red / PI;
+                }
+            }
+            albedo *= 2.0 * PI / (n_theta * n_phi) as f32;
+            assert!(albedo <= 1.01 && albedo > 0.8, "{}", albedo);
+        }
+    }
+}
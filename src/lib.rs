@@ -25,6 +25,45 @@ pub use aabb::*;
 mod material;
 pub use material::*;
 
+mod pattern;
+pub use pattern::*;
+
+mod uv;
+pub use uv::*;
+
+mod texture;
+pub use texture::*;
+
+mod frame;
+pub use frame::*;
+
+mod normal_map;
+pub use normal_map::*;
+
+mod microfacet;
+pub use microfacet::*;
+
+mod bsdf;
+pub use bsdf::*;
+
+mod toon;
+pub use toon::*;
+
+mod layered;
+pub use layered::*;
+
+mod subsurface;
+pub use subsurface::*;
+
+mod optics;
+pub use optics::*;
+
+mod spectrum;
+pub use spectrum::*;
+
+mod light;
+pub use light::*;
+
 mod point_light;
 pub use point_light::*;
 
@@ -34,12 +73,36 @@ pub use intersection::*;
 mod world;
 pub use world::*;
 
+mod stats;
+pub use stats::*;
+
 mod object;
 pub use object::*;
 
 mod camera;
 pub use camera::*;
 
+mod render;
+pub use render::*;
+
+mod rng;
+pub use rng::*;
+
+mod noise;
+pub use noise::*;
+
+mod progressive;
+pub use progressive::*;
+
+mod adaptive;
+pub use adaptive::*;
+
+mod scene;
+pub use scene::*;
+
+mod distributed;
+pub use distributed::*;
+
 #[cfg(test)]
 mod lib_test;
 
@@ -0,0 +1,88 @@
+use crate::{Color, Spectrum, Vec4};
+
+/// a source of direct light. `World` shades and tests shadows through this, for any kind of light
+pub trait Light {
+    /// light reaching `pos`. with a `wavelength` (nm, spectral rendering) the intensity is gray,
+    /// the light's emission at that wavelength
+    fn illuminate(&self, pos: Vec4, wavelength: Option<f32>) -> LightSample;
+}
+
+/// light arriving at a point from one light
+#[derive(Debug, Clone, Copy)]
+pub struct LightSample {
+    /// unit vector from the point toward the light
+    pub lightv: Vec4,
+    /// how far the light is along `lightv`: shadow rays stop there. infinite for a light at infinity
+    pub distance: f32,
+    pub intensity: Color,
+}
+
+/// `intensity`, or its value at `wavelength` (see `Light::illuminate`). `spectrum` replaces the
+/// upsampled `intensity` there when set
+pub(crate) fn spectral_intensity(intensity: Color, spectrum: Option<&dyn Spectrum>, wavelength: Option<f32>) -> Color {
+    match (wavelength, spectrum) {
+        (None, _) => intensity,
+        (Some(wavelength), Some(spectrum)) => Color::WHITE * spectrum.value(wavelength),
+        (Some(wavelength), None) => Color::WHITE * intensity.value(wavelength),
+    }
+}
+
+/// light from so far away that its rays are parallel, like the sun. it lights every point from the same
+/// direction at the same intensity, and only objects between the point and infinity cast shadows
+pub struct DirectionalLight {
+    /// the way the light travels, from the light toward the scene
+    pub direction: Vec4,
+    pub intensity: Color,
+    /// emission for spectral rendering, as with `PointLight::spectrum`
+    pub spectrum: Option<Box<dyn Spectrum>>,
+}
+
+impl DirectionalLight {
+    /// `direction` need not be unit length, but must not be zero
+    pub fn new(direction: Vec4, intensity: Color) -> Self {
+        Self {
+            direction: direction.normalize(),
+            intensity,
+            spectrum: None,
+        }
+    }
+}
+
+impl Light for DirectionalLight {
+    fn illuminate(&self, _pos: Vec4, wavelength: Option<f32>) -> LightSample {
+        LightSample {
+            lightv: -self.direction.normalize(),
+            distance: f32::INFINITY,
+            intensity: spectral_intensity(self.intensity, self.spectrum.as_deref(), wavelength),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{lib_test::assert_almost_eq_tuple, point, vector, Blackbody};
+
+    use super::*;
+
+    #[test]
+    fn directional_light_is_the_same_everywhere() {
+        let sun = DirectionalLight::new(vector(0.0, -2.0, 0.0), Color::WHITE);
+        for pos in [point(0.0, 0.0, 0.0), point(100.0, -50.0, 3.0)] {
+            let sample = sun.illuminate(pos, None);
+            assert_almost_eq_tuple(sample.lightv, vector(0.0, 1.0, 0.0));
+            assert_eq!(sample.distance, f32::INFINITY);
+        }
+    }
+
+    #[test]
+    fn spectrum_overrides_intensity() {
+        let mut sun = DirectionalLight::new(vector(0.0, -1.0, 0.0), Color::new(0.2, 0.2, 0.2));
+        sun.spectrum = Some(Box::new(Blackbody::new(2000.0, 1.0)));
+        assert_eq!(sun.illuminate(point(0.0, 0.0, 0.0), None).intensity.red, 0.2);
+
+        let red = sun.illuminate(point(0.0, 0.0, 0.0), Some(650.0)).intensity;
+        let blue = sun.illuminate(point(0.0, 0.0, 0.0), Some(450.0)).intensity;
+        assert!(red.red > blue.red);
+        assert_eq!(red.red, red.blue);
+    }
+}
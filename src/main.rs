@@ -1,9 +1,56 @@
+use std::io::{self, Write};
+use std::net::{TcpListener, ToSocketAddrs};
+use std::process;
+
 use raytrace_rs::{
-    point, vector, view_transform, Aabb, Angle, Camera, Canvas, Color, Material, MaterialPattern, PointLight, Sphere,
-    World,
+    point, render_with_progress, run_worker, vector, view_transform, Aabb, Angle, Camera, CancelToken, Canvas,
+    CheckerPattern, Color, Coordinator, Material, Matrix, Pattern, PointLight, Sphere, World,
 };
 
+const USAGE: &str = "usage: raytrace-rs                 (renders a demo scene to output.png)
+       raytrace-rs worker <listen addr>
+       raytrace-rs coordinator <scene file> <output png> <worker addr>...";
+
 fn main() {
+    let args: Vec<String> = std::env::args().collect();
+
+    let result = match (args.get(1).map(String::as_str), args.len()) {
+        (None, _) => {
+            render_demo();
+            Ok(())
+        }
+        (Some("worker"), 3) => worker(&args[2]),
+        (Some("coordinator"), len) if len >= 5 => coordinator(&args[2], &args[3], &args[4..]),
+        _ => {
+            eprintln!("{}", USAGE);
+            process::exit(2);
+        }
+    };
+
+    if let Err(e) = result {
+        eprintln!("error: {}", e);
+        process::exit(1);
+    }
+}
+
+fn worker(addr: &str) -> io::Result<()> {
+    let listener = TcpListener::bind(addr)?;
+    eprintln!("worker listening on {}", listener.local_addr()?);
+    run_worker(listener)
+}
+
+fn coordinator(scene_path: &str, output_path: &str, worker_addrs: &[String]) -> io::Result<()> {
+    let scene = std::fs::read_to_string(scene_path)?;
+    let mut workers = Vec::new();
+    for addr in worker_addrs {
+        workers.extend(addr.to_socket_addrs()?);
+    }
+    let canvas = Coordinator::new(workers).render(&scene, Some(1.0))?;
+    canvas.save_to_file(output_path);
+    Ok(())
+}
+
+fn render_demo() {
     let (width, height): (u32, u32) = (1600, 900);
 
     let eye = point(-3.0, 2.0, 5.5);
@@ -21,36 +68,47 @@ fn main() {
     let mut world = World::new();
 
     let light1 = PointLight::new(point(-5.0, 5.0, 5.0), Color::WHITE * 0.9);
-    world.add_pointlight(light1);
+    world.add_light(light1);
 
     let light2 = PointLight::new(point(5.0, 0.0, 5.0), Color::WHITE * 0.7);
-    world.add_pointlight(light2);
+    world.add_light(light2);
 
     let mut sphere1 = Sphere::new(point(-0.5, 1.0, 10.0), 1.0);
-    sphere1.mat = Material::new(Color::new(0.8, 0.4, 0.2), MaterialPattern::Solid, 0.2, 0.8, 1.0, 200.0);
+    sphere1.mat = Material::new(Color::new(0.8, 0.4, 0.2), 0.2, 0.8, 1.0, 200.0);
     world.add_object(sphere1);
 
     let mut sphere2 = Sphere::new(point(-1.4, 0.5, 9.0), 0.5);
-    sphere2.mat = Material::new(Color::new(0.2, 0.8, 0.4), MaterialPattern::Solid, 0.2, 0.6, 1.0, 200.0);
+    sphere2.mat = Material::new(Color::new(0.2, 0.8, 0.4), 0.2, 0.6, 1.0, 200.0);
     world.add_object(sphere2);
 
     let mut aabb1 = Aabb::new(point(-4.0, 0.0, -25.0), point(4.0, 100.0, 13.0), false);
-    aabb1.mat = Material::new(Color::new(0.3, 0.3, 0.3), MaterialPattern::Check, 0.2, 0.4, 0.3, 100.0);
+    aabb1.mat = Material::new(Color::new(0.3, 0.3, 0.3), 0.2, 0.4, 0.3, 100.0);
+    let mut floor_pattern = CheckerPattern::new(Color::new(0.3, 0.3, 0.3), Color::new(0.15, 0.15, 0.15));
+    floor_pattern
+        .set_transform(Matrix::scale(point(0.47, 0.47, 0.47)))
+        .unwrap();
+    aabb1.mat.pattern = Some(Box::new(floor_pattern));
     world.add_object(aabb1);
 
     let mut aabb2 = Aabb::new(point(1.0, 0.0, 9.0), point(2.0, 1.0, 10.0), true);
-    aabb2.mat = Material::new(Color::new(0.2, 0.6, 0.9), MaterialPattern::Solid, 0.2, 0.3, 1.0, 200.0);
+    aabb2.mat = Material::new(Color::new(0.2, 0.6, 0.9), 0.2, 0.3, 1.0, 200.0);
     world.add_object(aabb2);
 
     let mut canvas = Canvas::new(width, height, Some(1.0));
 
-    for ix in 0..width {
-        for iy in 0..height {
-            let ray = camera.get_ray(ix, iy);
-            let color = world.shade(&ray, camera.dir());
-            canvas.write_pixel(ix, iy, color);
-        }
-    }
+    world.enable_stats(true);
+    render_with_progress(&world, &camera, &mut canvas, &CancelToken::new(), |p| {
+        let eta = p.eta().map(|eta| eta.as_secs()).unwrap_or(0);
+        eprint!(
+            "\r{:5.1}% {:8.0} rays/s eta {}s   ",
+            p.fraction() * 100.0,
+            p.rays_per_sec(),
+            eta
+        );
+        std::io::stderr().flush().unwrap();
+    });
+    eprintln!();
+    eprintln!("{}", world.take_stats());
 
     canvas.save_to_file("output.png");
 }
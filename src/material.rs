@@ -1,37 +1,56 @@
-use crate::{Color, Vec4};
+use crate::{
+    Bsdf, ClearCoat, Color, Dispersion, Intersection, NormalMapping, Pattern, Phong, Spectrum, Subsurface, ThinFilm,
+    Uv, Vec4,
+};
 
 #[derive(Debug)]
 pub struct Material {
     pub color: Color,
-    pub pattern: MaterialPattern,
+    /// overrides `color` when set
+    pub pattern: Option<Box<dyn Pattern>>,
+    /// measured reflectance for spectral rendering. the color (or pattern) is upsampled when None
+    pub spectrum: Option<Box<dyn Spectrum>>,
+    /// bump / normal map for shading
+    pub normal_mapping: Option<Box<dyn NormalMapping>>,
+    /// how light is scattered at the surface. `Phong` by default
+    pub bsdf: Box<dyn Bsdf>,
+    /// clear layer over the shading model (car paint, varnish)
+    pub clearcoat: Option<ClearCoat>,
+    /// light diffused under the surface (skin, wax, marble). replaces the diffuse light of any `bsdf`: it is
+    /// shaded with a black base color, so only its specular part is kept (a metallic base loses its tint)
+    pub subsurface: Option<Subsurface>,
     pub ambient: f32,
     pub diffuse: f32,
+    /// oren-nayar roughness (std deviation of the facet angle, radians) of the phong diffuse term.
+    /// 0 = lambert. clay and concrete are around 0.3 - 0.5
+    pub diffuse_roughness: f32,
     pub specular: f32,
     pub shininess: f32,
-}
-
-#[derive(Debug, Clone, Copy)]
-pub enum MaterialPattern {
-    Solid,
-    Check,
+    /// 0 = no reflection, 1 = perfect mirror
+    pub reflective: f32,
+    /// 0 = opaque, 1 = fully transparent
+    pub transparency: f32,
+    /// 1.0 for vacuum, 1.5 for glass
+    pub refractive_index: f32,
+    /// wavelength dependent index for prisms and gems. `refractive_index` is still used for
+    /// rays that carry no wavelength, so keep it at the index for green light
+    pub dispersion: Option<Dispersion>,
+    /// iridescent film on the surface. its reflectance replaces `reflective` and the schlick term
+    pub thin_film: Option<ThinFilm>,
+    /// light given off by the surface itself, independent of any light source
+    pub emission: Color,
+    pub emission_strength: f32,
 }
 
 impl Material {
-    pub fn new(
-        color: Color,
-        pattern: MaterialPattern,
-        ambient: f32,
-        diffuse: f32,
-        specular: f32,
-        shininess: f32,
-    ) -> Self {
+    pub fn new(color: Color, ambient: f32, diffuse: f32, specular: f32, shininess: f32) -> Self {
         Self {
             color,
-            pattern,
             ambient,
             diffuse,
             specular,
             shininess,
+            ..Default::default()
         }
     }
 }
@@ -40,43 +59,84 @@ impl Default for Material {
     fn default() -> Self {
         Self {
             color: Color::new(1.0, 1.0, 1.0),
-            pattern: MaterialPattern::Solid,
+            pattern: None,
+            spectrum: None,
+            normal_mapping: None,
+            bsdf: Box::new(Phong),
+            clearcoat: None,
+            subsurface: None,
             ambient: 0.1,
             diffuse: 0.9,
+            diffuse_roughness: 0.0,
             specular: 0.9,
             shininess: 200.0,
+            reflective: 0.0,
+            transparency: 0.0,
+            refractive_index: 1.0,
+            dispersion: None,
+            thin_film: None,
+            emission: Color::BLACK,
+            emission_strength: 1.0,
         }
     }
 }
 
 impl Material {
-    pub fn color(&self, pos: Vec4) -> Color {
-        match self.pattern {
-            MaterialPattern::Solid => self.color,
+    /// surface color at a point in object space with surface coordinates `uv`
+    pub fn color(&self, object_pos: Vec4, uv: Uv) -> Color {
+        match &self.pattern {
+            Some(pattern) => pattern.color_at_uv(object_pos, uv),
+            None => self.color,
+        }
+    }
+
+    /// refractive index for a ray of `wavelength` nm
+    pub fn refractive_index_at(&self, wavelength: Option<f32>) -> f32 {
+        match (self.dispersion, wavelength) {
+            (Some(dispersion), Some(wavelength)) => dispersion.index_at(wavelength),
+            _ => self.refractive_index,
+        }
+    }
+
+    /// reflectance at `wavelength` nm, for spectral rendering
+    pub fn reflectance_at(&self, object_pos: Vec4, uv: Uv, wavelength: f32) -> f32 {
+        match &self.spectrum {
+            Some(spectrum) => spectrum.value(wavelength),
+            None => self.color(object_pos, uv).value(wavelength),
+        }
+    }
 
-            MaterialPattern::Check => {
-                let x = is_check_dark(pos[0]);
-                let y = is_check_dark(pos[1]);
-                let z = is_check_dark(pos[2]);
+    pub fn emitted(&self) -> Color {
+        self.emission * self.emission_strength
+    }
 
-                let count = [x, y, z].iter().filter(|v| **v).count();
+    /// `emitted` at `wavelength` nm
+    pub fn emitted_at(&self, wavelength: f32) -> f32 {
+        self.emission.value(wavelength) * self.emission_strength
+    }
 
-                if count % 2 == 0 {
-                    self.color
-                } else {
-                    self.color * 0.5
-                }
-            }
+    /// normal used for lighting at the hit. the geometric one without a normal mapping
+    pub fn shading_normal(&self, hit: &Intersection) -> Vec4 {
+        match &self.normal_mapping {
+            Some(mapping) => mapping.shading_normal(hit),
+            None => hit.normalv,
         }
     }
 }
 
-fn is_check_dark(v: f32) -> bool {
-    let x = v / 0.47;
-    let mut xi = (x.abs() as i32) % 2 == 0;
-    if x.is_sign_negative() {
-        xi = !xi;
-    }
+#[cfg(test)]
+mod tests {
+    use crate::{lib_test::assert_almost_eq_color, point, StripePattern};
+
+    use super::*;
 
-    xi
+    #[test]
+    fn pattern_overrides_color() {
+        let mut material = Material::default();
+        assert_almost_eq_color(material.color(point(1.5, 0.0, 0.0), (0.0, 0.0)), Color::WHITE);
+
+        material.pattern = Some(Box::new(StripePattern::new(Color::WHITE, Color::BLACK)));
+        assert_almost_eq_color(material.color(point(0.5, 0.0, 0.0), (0.0, 0.0)), Color::WHITE);
+        assert_almost_eq_color(material.color(point(1.5, 0.0, 0.0), (0.0, 0.0)), Color::BLACK);
+    }
 }
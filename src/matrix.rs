@@ -91,7 +91,7 @@ impl Matrix4 {
 
     pub fn cofactor(&self, row: usize, column: usize) -> f32 {
         let v = self.minor(row, column);
-        if (row + column) % 2 == 0 {
+        if (row + column).is_multiple_of(2) {
             v
         } else {
             -v
@@ -202,7 +202,7 @@ impl Matrix3 {
 
     pub fn cofactor(&self, row: usize, column: usize) -> f32 {
         let v = self.minor(row, column);
-        if (row + column) % 2 == 0 {
+        if (row + column).is_multiple_of(2) {
             v
         } else {
             -v
@@ -243,7 +243,7 @@ impl Matrix2 {
         let row = if row == 0 { 1 } else { 0 };
         let column = if column == 0 { 1 } else { 0 };
         let v = self[(row, column)];
-        if (row + column) % 2 == 0 {
+        if (row + column).is_multiple_of(2) {
             v
         } else {
             -v
@@ -343,10 +343,7 @@ impl<const R: usize, const C: usize> Mul<Tuple<C>> for Matrix<R, C> {
 macro_rules! mat {
     ( $($x:expr), * $(,)?) => {
 		{
-			let mut v = Vec::new();
-			$(
-				v.push($x);
-			)*
+			let v = vec![$($x),*];
 			Matrix::new(&v)
 		}
 	};
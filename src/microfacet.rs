@@ -0,0 +1,245 @@
+use std::f32::consts::PI;
+
+use crate::{Color, ShadingFrame, Vec4};
+
+/// roughness below this turns point light highlights into sub-pixel sparks (and 0 divides by 0)
+pub const MIN_ROUGHNESS: f32 = 0.02;
+
+/// reflectance at normal incidence of common dielectrics (plastic, glass, ...)
+pub const DIELECTRIC_F0: f32 = 0.04;
+
+/// GGX (Trowbridge-Reitz) normal distribution. `alpha`: roughness squared
+pub fn ggx_distribution(n_dot_h: f32, alpha: f32) -> f32 {
+    let a2 = alpha * alpha;
+    let d = n_dot_h * n_dot_h * (a2 - 1.0) + 1.0;
+    a2 / (PI * d * d)
+}
+
+/// smith masking of one direction for the GGX distribution
+pub fn smith_g1(n_dot_v: f32, alpha: f32) -> f32 {
+    let a2 = alpha * alpha;
+    2.0 * n_dot_v / (n_dot_v + (a2 + (1.0 - a2) * n_dot_v * n_dot_v).sqrt())
+}
+
+/// anisotropic GGX. `h`: half vector in (tangent, bitangent, normal) coordinates
+pub fn ggx_anisotropic_distribution(h: Vec4, alpha_u: f32, alpha_v: f32) -> f32 {
+    let (x, y, z) = (h[0] / alpha_u, h[1] / alpha_v, h[2]);
+    let d = x * x + y * y + z * z;
+    1.0 / (PI * alpha_u * alpha_v * d * d)
+}
+
+/// smith masking of one direction `v` (tangent, bitangent, normal coordinates) for anisotropic GGX
+pub fn smith_g1_anisotropic(v: Vec4, alpha_u: f32, alpha_v: f32) -> f32 {
+    let (x, y, z) = (v[0] * alpha_u, v[1] * alpha_v, v[2]);
+    let lambda = (-1.0 + (1.0 + (x * x + y * y) / (z * z)).sqrt()) / 2.0;
+    1.0 / (1.0 + lambda)
+}
+
+/// schlick's fresnel with a colored reflectance at normal incidence (metals)
+pub fn fresnel_schlick(f0: Color, cos: f32) -> Color {
+    f0 + (Color::WHITE - f0) * (1.0 - cos).clamp(0.0, 1.0).powi(5)
+}
+
+/// cook-torrance brdf with GGX / smith / schlick plus a lambert diffuse lobe that only gets
+/// the light the specular layer lets through on the way in and on the way out, so the sum
+/// never reflects more than arrives. metals have no diffuse and tint their specular.
+/// `lightv`, `viewv`: unit vectors from the surface toward the light / the eye
+pub fn cook_torrance(
+    base_color: Color,
+    metallic: f32,
+    roughness: f32,
+    normalv: Vec4,
+    lightv: Vec4,
+    viewv: Vec4,
+) -> Color {
+    let n_dot_l = normalv.dot(lightv);
+    let n_dot_v = normalv.dot(viewv);
+    if n_dot_l <= 0.0 || n_dot_v <= 0.0 {
+        return Color::BLACK;
+    }
+
+    let roughness = roughness.clamp(MIN_ROUGHNESS, 1.0);
+    let alpha = roughness * roughness;
+    let halfv = (lightv + viewv).normalize();
+    let n_dot_h = normalv.dot(halfv).max(0.0);
+    let v_dot_h = viewv.dot(halfv).max(0.0);
+
+    let d = ggx_distribution(n_dot_h, alpha);
+    let g = smith_g1(n_dot_l, alpha) * smith_g1(n_dot_v, alpha);
+    combine_layers(base_color, metallic, (n_dot_l, n_dot_v, v_dot_h), d * g)
+}
+
+/// `cook_torrance` with a GGX lobe stretched along the frame's tangent / bitangent.
+/// `(roughness_u, roughness_v)`: roughness along the tangent and the bitangent
+pub fn cook_torrance_anisotropic(
+    base_color: Color,
+    metallic: f32,
+    (roughness_u, roughness_v): (f32, f32),
+    frame: &ShadingFrame,
+    lightv: Vec4,
+    viewv: Vec4,
+) -> Color {
+    let l = frame.to_local(lightv);
+    let v = frame.to_local(viewv);
+    if l[2] <= 0.0 || v[2] <= 0.0 {
+        return Color::BLACK;
+    }
+
+    let alpha_u = roughness_u.clamp(MIN_ROUGHNESS, 1.0).powi(2);
+    let alpha_v = roughness_v.clamp(MIN_ROUGHNESS, 1.0).powi(2);
+    let h = (l + v).normalize();
+    let v_dot_h = v.dot(h).max(0.0);
+
+    let d = ggx_anisotropic_distribution(h, alpha_u, alpha_v);
+    let g = smith_g1_anisotropic(l, alpha_u, alpha_v) * smith_g1_anisotropic(v, alpha_u, alpha_v);
+    combine_layers(base_color, metallic, (l[2], v[2], v_dot_h), d * g)
+}
+
+/// specular lobe with `d_times_g` = D * G plus the diffuse under it. shared by the microfacet brdfs
+fn combine_layers(
+    base_color: Color,
+    metallic: f32,
+    (n_dot_l, n_dot_v, v_dot_h): (f32, f32, f32),
+    d_times_g: f32,
+) -> Color {
+    let f0 = Color::new(DIELECTRIC_F0, DIELECTRIC_F0, DIELECTRIC_F0) * (1.0 - metallic) + base_color * metallic;
+    let specular = fresnel_schlick(f0, v_dot_h) * (d_times_g / (4.0 * n_dot_l * n_dot_v));
+
+    let transmitted = (Color::WHITE - fresnel_schlick(f0, n_dot_l)) * (Color::WHITE - fresnel_schlick(f0, n_dot_v));
+    let diffuse = transmitted * base_color * ((1.0 - metallic) / PI);
+    diffuse + specular
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{lib_test::assert_almost_eq_f32, vector};
+
+    use super::*;
+
+    /// hemisphere integral of brdf * cos for light coming from `viewv`'s mirror side
+    fn directional_albedo(base: Color, metallic: f32, roughness: f32, viewv: Vec4) -> Color {
+        let normalv = vector(0.0, 1.0, 0.0);
+        let (n_theta, n_phi) = (256, 128);
+        let mut sum = Color::BLACK;
+        for i in 0..n_theta {
+            // uniform in cos theta
+            let cos_theta = (i as f32 + 0.5) / n_theta as f32;
+            let sin_theta = (1.0 - cos_theta * cos_theta).sqrt();
+            for j in 0..n_phi {
+                let phi = 2.0 * PI * (j as f32 + 0.5) / n_phi as f32;
+                let lightv = vector(sin_theta * phi.cos(), cos_theta, sin_theta * phi.sin());
+                sum += cook_torrance(base, metallic, roughness, normalv, lightv, viewv) * cos_theta;
+            }
+        }
+        sum * (2.0 * PI / (n_theta * n_phi) as f32)
+    }
+
+    #[test]
+    fn ggx_normalized() {
+        // projected area of the microfacets is 1: integral of D(h) cos(h) over the hemisphere
+        for alpha in [0.1, 0.3, 0.7, 1.0] {
+            let n = 4096;
+            let integral: f32 = (0..n)
+                .map(|i| {
+                    let cos = (i as f32 + 0.5) / n as f32;
+                    ggx_distribution(cos, alpha) * cos
+                })
+                .sum::<f32>()
+                * (2.0 * PI / n as f32);
+            assert!((integral - 1.0).abs() < 0.02, "alpha {}: {}", alpha, integral);
+        }
+    }
+
+    #[test]
+    fn smith_is_one_at_normal_incidence() {
+        assert_almost_eq_f32(smith_g1(1.0, 0.5), 1.0);
+        assert!(smith_g1(0.1, 0.5) < smith_g1(0.5, 0.5));
+    }
+
+    #[test]
+    fn fresnel_goes_white_at_grazing() {
+        let f0 = Color::new(0.9, 0.6, 0.2);
+        let head_on = fresnel_schlick(f0, 1.0);
+        let grazing = fresnel_schlick(f0, 0.0);
+        assert_almost_eq_f32(head_on.green, 0.6);
+        assert_almost_eq_f32(grazing.blue, 1.0);
+    }
+
+    #[test]
+    fn energy_conserving() {
+        // smoother lobes are too narrow for this quadrature
+        let viewv = vector(0.0, 1.0, 0.0);
+        let grazing = vector(0.9, 0.2, 0.0).normalize();
+        for metallic in [0.0, 1.0] {
+            for roughness in [0.4, 0.7, 1.0] {
+                for view in [viewv, grazing] {
+                    let albedo = directional_albedo(Color::WHITE, metallic, roughness, view);
+                    assert!(
+                        albedo.red <= 1.01,
+                        "metallic {} roughness {}: {:?}",
+                        metallic,
+                        roughness,
+                        albedo
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn metal_has_no_diffuse() {
+        let normalv = vector(0.0, 1.0, 0.0);
+        let viewv = vector(0.0, 1.0, 0.0);
+        // light far from the mirror direction of a smooth surface: only diffuse would show
+        let lightv = vector(1.0, 0.2, 0.0).normalize();
+        let red = Color::new(1.0, 0.0, 0.0);
+        let metal = cook_torrance(red, 1.0, 0.1, normalv, lightv, viewv);
+        let plastic = cook_torrance(red, 0.0, 0.1, normalv, lightv, viewv);
+        assert!(metal.red < 1e-3);
+        assert!(plastic.red > 0.15);
+    }
+
+    #[test]
+    fn anisotropic_with_equal_roughness_is_isotropic() {
+        let frame = ShadingFrame::new(vector(0.0, 1.0, 0.0), vector(1.0, 0.0, 0.0));
+        let viewv = vector(0.3, 0.8, -0.2).normalize();
+        for lightv in [vector(-0.4, 0.7, 0.3), vector(0.1, 0.2, 0.9), vector(0.0, 1.0, 0.0)] {
+            let lightv = lightv.normalize();
+            let base = Color::new(0.8, 0.5, 0.3);
+            let iso = cook_torrance(base, 0.5, 0.4, frame.normalv, lightv, viewv);
+            let aniso = cook_torrance_anisotropic(base, 0.5, (0.4, 0.4), &frame, lightv, viewv);
+            assert_almost_eq_f32(aniso.red, iso.red);
+            assert_almost_eq_f32(aniso.blue, iso.blue);
+        }
+    }
+
+    #[test]
+    fn anisotropic_highlight_stretches_along_rough_direction() {
+        // smooth along the tangent (x), rough along the bitangent (z)
+        let frame = ShadingFrame::new(vector(0.0, 1.0, 0.0), vector(1.0, 0.0, 0.0));
+        let viewv = vector(0.0, 1.0, 0.0);
+        let tilt = |x: f32, z: f32| vector(x, 1.0, z).normalize();
+        let brdf = |lightv| cook_torrance_anisotropic(Color::WHITE, 1.0, (0.1, 0.5), &frame, lightv, viewv).red;
+
+        assert!(brdf(tilt(0.0, 0.3)) > 10.0 * brdf(tilt(0.3, 0.0)));
+    }
+
+    #[test]
+    fn anisotropic_ggx_normalized() {
+        // integral of D(h) cos(h) over the hemisphere is 1 for any pair of roughnesses
+        let (alpha_u, alpha_v) = (0.2, 0.6);
+        let (n_theta, n_phi) = (1024, 256);
+        let mut integral = 0.0;
+        for i in 0..n_theta {
+            let cos = (i as f32 + 0.5) / n_theta as f32;
+            let sin = (1.0 - cos * cos).sqrt();
+            for j in 0..n_phi {
+                let phi = 2.0 * PI * (j as f32 + 0.5) / n_phi as f32;
+                let h = vector(sin * phi.cos(), sin * phi.sin(), cos);
+                integral += ggx_anisotropic_distribution(h, alpha_u, alpha_v) * cos;
+            }
+        }
+        integral *= 2.0 * PI / (n_theta * n_phi) as f32;
+        assert!((integral - 1.0).abs() < 0.02, "{}", integral);
+    }
+}
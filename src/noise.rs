@@ -0,0 +1,304 @@
+use std::fmt::Debug;
+
+use crate::{Rng, Vec4};
+
+/// scalar noise over 3d space. only x, y, z of `pos` are used
+pub trait Noise: Debug {
+    fn noise(&self, pos: Vec4) -> f32;
+}
+
+/// sum of `octaves` layers of noise, each twice the frequency and half the amplitude
+/// of the previous one. normalized so the range matches the input noise. 0 octaves give 0
+pub fn fbm<N: Noise + ?Sized>(noise: &N, pos: Vec4, octaves: u32) -> f32 {
+    octave_sum(noise, pos, octaves, |value| value)
+}
+
+/// like `fbm` but sums absolute values, giving sharp creases (fire, veins). [0, 1] for perlin / simplex
+pub fn turbulence<N: Noise + ?Sized>(noise: &N, pos: Vec4, octaves: u32) -> f32 {
+    octave_sum(noise, pos, octaves, f32::abs)
+}
+
+/// normalized sum of `layer` applied to each octave's noise
+fn octave_sum<N: Noise + ?Sized>(noise: &N, pos: Vec4, octaves: u32, layer: impl Fn(f32) -> f32) -> f32 {
+    if octaves == 0 {
+        return 0.0;
+    }
+
+    let mut sum = 0.0;
+    let mut amplitude = 1.0;
+    let mut total = 0.0;
+    let mut p = pos;
+    for _ in 0..octaves {
+        sum += layer(noise.noise(p)) * amplitude;
+        total += amplitude;
+        amplitude *= 0.5;
+        p = p * 2.0;
+    }
+    sum / total
+}
+
+/// seeded 0..256 permutation, repeated twice so `perm[perm[x] + y]` needs no wrapping
+fn permutation(seed: u64) -> Vec<u8> {
+    let mut perm: Vec<u8> = (0..=255).collect();
+    let mut rng = Rng::new(seed);
+    for i in (1..perm.len()).rev() {
+        let j = (rng.next_u32() as usize) % (i + 1);
+        perm.swap(i, j);
+    }
+    perm.extend_from_within(..);
+    perm
+}
+
+const GRADIENTS: [[f32; 3]; 12] = [
+    [1.0, 1.0, 0.0],
+    [-1.0, 1.0, 0.0],
+    [1.0, -1.0, 0.0],
+    [-1.0, -1.0, 0.0],
+    [1.0, 0.0, 1.0],
+    [-1.0, 0.0, 1.0],
+    [1.0, 0.0, -1.0],
+    [-1.0, 0.0, -1.0],
+    [0.0, 1.0, 1.0],
+    [0.0, -1.0, 1.0],
+    [0.0, 1.0, -1.0],
+    [0.0, -1.0, -1.0],
+];
+
+fn grad_dot(hash: u8, x: f32, y: f32, z: f32) -> f32 {
+    let g = GRADIENTS[hash as usize % 12];
+    g[0] * x + g[1] * y + g[2] * z
+}
+
+/// classic gradient noise. about [-1, 1], 0 on every integer lattice point
+#[derive(Debug, Clone)]
+pub struct Perlin {
+    perm: Vec<u8>,
+}
+
+impl Perlin {
+    pub fn new(seed: u64) -> Self {
+        Self {
+            perm: permutation(seed),
+        }
+    }
+
+    fn hash(&self, x: i32, y: i32, z: i32) -> u8 {
+        let p = &self.perm;
+        p[p[p[(x & 255) as usize] as usize + (y & 255) as usize] as usize + (z & 255) as usize]
+    }
+}
+
+impl Noise for Perlin {
+    fn noise(&self, pos: Vec4) -> f32 {
+        let (xf, yf, zf) = (pos[0].floor(), pos[1].floor(), pos[2].floor());
+        let (xi, yi, zi) = (xf as i32, yf as i32, zf as i32);
+        let (x, y, z) = (pos[0] - xf, pos[1] - yf, pos[2] - zf);
+
+        let fade = |t: f32| t * t * t * (t * (t * 6.0 - 15.0) + 10.0);
+        let lerp = |a: f32, b: f32, t: f32| a + (b - a) * t;
+        let (u, v, w) = (fade(x), fade(y), fade(z));
+
+        let corner = |i: i32, j: i32, k: i32| {
+            let h = self.hash(xi + i, yi + j, zi + k);
+            grad_dot(h, x - i as f32, y - j as f32, z - k as f32)
+        };
+
+        lerp(
+            lerp(
+                lerp(corner(0, 0, 0), corner(1, 0, 0), u),
+                lerp(corner(0, 1, 0), corner(1, 1, 0), u),
+                v,
+            ),
+            lerp(
+                lerp(corner(0, 0, 1), corner(1, 0, 1), u),
+                lerp(corner(0, 1, 1), corner(1, 1, 1), u),
+                v,
+            ),
+            w,
+        )
+    }
+}
+
+/// simplex noise: cheaper than perlin in 3d and without its axis aligned artifacts. about [-1, 1]
+#[derive(Debug, Clone)]
+pub struct Simplex {
+    perm: Vec<u8>,
+}
+
+impl Simplex {
+    pub fn new(seed: u64) -> Self {
+        Self {
+            perm: permutation(seed),
+        }
+    }
+
+    fn hash(&self, x: i32, y: i32, z: i32) -> u8 {
+        let p = &self.perm;
+        p[p[p[(x & 255) as usize] as usize + (y & 255) as usize] as usize + (z & 255) as usize]
+    }
+}
+
+impl Noise for Simplex {
+    fn noise(&self, pos: Vec4) -> f32 {
+        const F3: f32 = 1.0 / 3.0;
+        const G3: f32 = 1.0 / 6.0;
+        let (x, y, z) = (pos[0], pos[1], pos[2]);
+
+        // skew into the simplex grid to find the containing cell
+        let s = (x + y + z) * F3;
+        let (i, j, k) = ((x + s).floor(), (y + s).floor(), (z + s).floor());
+        let t = (i + j + k) * G3;
+        let (x0, y0, z0) = (x - (i - t), y - (j - t), z - (k - t));
+
+        // which of the six tetrahedra of the cube we are in
+        let (o1, o2) = if x0 >= y0 {
+            if y0 >= z0 {
+                ((1, 0, 0), (1, 1, 0))
+            } else if x0 >= z0 {
+                ((1, 0, 0), (1, 0, 1))
+            } else {
+                ((0, 0, 1), (1, 0, 1))
+            }
+        } else if y0 < z0 {
+            ((0, 0, 1), (0, 1, 1))
+        } else if x0 < z0 {
+            ((0, 1, 0), (0, 1, 1))
+        } else {
+            ((0, 1, 0), (1, 1, 0))
+        };
+
+        let (i, j, k) = (i as i32, j as i32, k as i32);
+        let corners = [((0, 0, 0), 0.0), (o1, G3), (o2, 2.0 * G3), ((1, 1, 1), 3.0 * G3)];
+
+        let mut sum = 0.0;
+        for ((di, dj, dk), offset) in corners {
+            let (cx, cy, cz) = (
+                x0 - di as f32 + offset,
+                y0 - dj as f32 + offset,
+                z0 - dk as f32 + offset,
+            );
+            let falloff = 0.6 - cx * cx - cy * cy - cz * cz;
+            if falloff > 0.0 {
+                let h = self.hash(i + di, j + dj, k + dk);
+                sum += falloff.powi(4) * grad_dot(h, cx, cy, cz);
+            }
+        }
+
+        32.0 * sum
+    }
+}
+
+/// cellular noise: distance to the nearest of randomly scattered feature points,
+/// one per unit cell. 0 at a feature point, rarely above 1
+#[derive(Debug, Clone)]
+pub struct Worley {
+    seed: u64,
+}
+
+impl Worley {
+    pub fn new(seed: u64) -> Self {
+        Self { seed }
+    }
+
+    fn feature_point(&self, x: i32, y: i32, z: i32) -> [f32; 3] {
+        let cell = (x as u32 as u64) ^ ((y as u32 as u64) << 21) ^ ((z as u32 as u64) << 42);
+        let mut rng = Rng::new(self.seed ^ cell.wrapping_mul(0x9E37_79B9_7F4A_7C15));
+        [
+            x as f32 + rng.next_f32(),
+            y as f32 + rng.next_f32(),
+            z as f32 + rng.next_f32(),
+        ]
+    }
+}
+
+impl Noise for Worley {
+    fn noise(&self, pos: Vec4) -> f32 {
+        let (xi, yi, zi) = (pos[0].floor() as i32, pos[1].floor() as i32, pos[2].floor() as i32);
+
+        let mut nearest = f32::MAX;
+        for i in -1..=1 {
+            for j in -1..=1 {
+                for k in -1..=1 {
+                    let f = self.feature_point(xi + i, yi + j, zi + k);
+                    let (dx, dy, dz) = (f[0] - pos[0], f[1] - pos[1], f[2] - pos[2]);
+                    nearest = nearest.min(dx * dx + dy * dy + dz * dz);
+                }
+            }
+        }
+
+        nearest.sqrt()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{lib_test::assert_almost_eq_f32, point};
+
+    use super::*;
+
+    fn sample_points() -> impl Iterator<Item = Vec4> {
+        (0..500).map(|i| {
+            let i = i as f32;
+            point(i * 0.173 - 20.0, i * 0.311 - 7.0, i * -0.057 + 3.0)
+        })
+    }
+
+    #[test]
+    fn perlin_fixed_seed() {
+        let perlin = Perlin::new(1);
+        assert_almost_eq_f32(perlin.noise(point(0.5, 0.5, 0.5)), -0.25);
+        assert_almost_eq_f32(perlin.noise(point(1.25, -3.7, 10.1)), -0.284077);
+        assert_almost_eq_f32(perlin.noise(point(3.0, 4.0, 5.0)), 0.0);
+    }
+
+    #[test]
+    fn simplex_fixed_seed() {
+        let simplex = Simplex::new(1);
+        assert_almost_eq_f32(simplex.noise(point(0.2, 0.7, 0.4)), -0.074879);
+        assert_almost_eq_f32(simplex.noise(point(1.25, -3.7, 10.1)), 0.543148);
+    }
+
+    #[test]
+    fn worley_fixed_seed() {
+        let worley = Worley::new(1);
+        assert_almost_eq_f32(worley.noise(point(0.5, 0.5, 0.5)), 0.554458);
+        assert_almost_eq_f32(worley.noise(point(1.25, -3.7, 10.1)), 0.345823);
+    }
+
+    #[test]
+    fn seed_changes_output() {
+        let p = point(1.25, -3.7, 10.1);
+        assert!(Perlin::new(1).noise(p) != Perlin::new(2).noise(p));
+        assert!(Simplex::new(1).noise(p) != Simplex::new(2).noise(p));
+        assert!(Worley::new(1).noise(p) != Worley::new(2).noise(p));
+    }
+
+    #[test]
+    fn noise_ranges() {
+        let noises: [Box<dyn Noise>; 2] = [Box::new(Perlin::new(7)), Box::new(Simplex::new(7))];
+        for noise in &noises {
+            for p in sample_points() {
+                let n = noise.noise(p);
+                assert!((-1.0..=1.0).contains(&n), "{:?} out of range: {}", noise, n);
+            }
+        }
+
+        let worley = Worley::new(7);
+        for p in sample_points() {
+            assert!((0.0..=3.0_f32.sqrt()).contains(&worley.noise(p)));
+        }
+    }
+
+    #[test]
+    fn fbm_and_turbulence() {
+        let perlin = Perlin::new(3);
+        for p in sample_points() {
+            assert_almost_eq_f32(fbm(&perlin, p, 1), perlin.noise(p));
+            assert!((-1.0..=1.0).contains(&fbm(&perlin, p, 5)));
+            assert!((0.0..=1.0).contains(&turbulence(&perlin, p, 5)));
+        }
+        assert_almost_eq_f32(fbm(&perlin, point(0.3, 0.6, 0.9), 4), 0.088381);
+        assert_eq!(fbm(&perlin, point(0.3, 0.6, 0.9), 0), 0.0);
+        assert_eq!(turbulence(&perlin, point(0.3, 0.6, 0.9), 0), 0.0);
+    }
+}
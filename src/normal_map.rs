@@ -0,0 +1,165 @@
+use std::fmt;
+use std::path::Path;
+
+use crate::{empty_image_error, vector, ImageTexture, Intersection, Noise, Pattern, ShadingFrame, Vec4};
+
+/// changes the normal used for shading without changing the geometry.
+/// the geometric `Intersection::normalv` is still used for shadow rays and surface offsets
+pub trait NormalMapping: fmt::Debug {
+    /// unit shading normal at the hit, on the same side as `hit.normalv`
+    fn shading_normal(&self, hit: &Intersection) -> Vec4;
+}
+
+/// bump from a scalar height field over object space. the normal tilts away from
+/// where the height rises, `strength` scales the slope
+pub struct BumpMap {
+    height: Box<dyn Fn(Vec4) -> f32>,
+    pub strength: f32,
+    /// finite difference step in object space
+    pub epsilon: f32,
+}
+
+impl BumpMap {
+    pub fn new<F: Fn(Vec4) -> f32 + 'static>(height: F, strength: f32) -> Self {
+        Self {
+            height: Box::new(height),
+            strength,
+            epsilon: 0.001,
+        }
+    }
+
+    /// noise sampled at `frequency` times the object space position
+    pub fn from_noise<N: Noise + 'static>(noise: N, frequency: f32, strength: f32) -> Self {
+        Self::new(move |pos| noise.noise(pos * frequency), strength)
+    }
+
+    fn gradient(&self, pos: Vec4) -> Vec4 {
+        let e = self.epsilon;
+        let diff = |d: Vec4| ((self.height)(pos + d) - (self.height)(pos - d)) / (2.0 * e);
+        vector(
+            diff(vector(e, 0.0, 0.0)),
+            diff(vector(0.0, e, 0.0)),
+            diff(vector(0.0, 0.0, e)),
+        )
+    }
+}
+
+impl fmt::Debug for BumpMap {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("BumpMap")
+            .field("strength", &self.strength)
+            .field("epsilon", &self.epsilon)
+            .finish()
+    }
+}
+
+impl NormalMapping for BumpMap {
+    /// object space is only translated / uniformly scaled from world space,
+    /// so the object space gradient can be used as a world direction
+    fn shading_normal(&self, hit: &Intersection) -> Vec4 {
+        let n = hit.normalv;
+        let gradient = self.gradient(hit.object_pos);
+        let surface_gradient = gradient - n * gradient.dot(n);
+        (n - surface_gradient * self.strength).normalize()
+    }
+}
+
+/// tangent space normal map: rgb = (tangent, bitangent, normal) * 0.5 + 0.5.
+/// looked up like a texture, so `texture.mapping` picks the uv
+#[derive(Debug)]
+pub struct NormalMap {
+    pub texture: ImageTexture,
+    /// 0 = flat, 1 = as authored
+    pub strength: f32,
+}
+
+impl NormalMap {
+    /// None for an empty image
+    pub fn new(image: &image::RgbImage) -> Option<Self> {
+        // normal maps store directions, not colors: no gamma
+        Some(Self {
+            texture: ImageTexture::new(image, Some(1.0))?,
+            strength: 1.0,
+        })
+    }
+
+    pub fn open<P: AsRef<Path>>(path: P) -> image::ImageResult<Self> {
+        Self::new(&image::open(path)?.to_rgb8()).ok_or_else(empty_image_error)
+    }
+}
+
+impl NormalMapping for NormalMap {
+    fn shading_normal(&self, hit: &Intersection) -> Vec4 {
+        let c = self.texture.color_at_uv(hit.object_pos, hit.uv);
+        let local = vector(
+            (c.red * 2.0 - 1.0) * self.strength,
+            (c.green * 2.0 - 1.0) * self.strength,
+            c.blue * 2.0 - 1.0,
+        );
+
+        ShadingFrame::new(hit.normalv, hit.tangent).to_world(local).normalize()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{lib_test::assert_almost_eq_tuple, point, Material, Perlin};
+
+    use super::*;
+
+    fn hit_on_front(material: &Material) -> Intersection<'_> {
+        // front (-z) face of a box: u along +x, v along +y
+        let mut hit = Intersection::new(1.0, point(0.3, 0.4, 0.0), vector(0.0, 0.0, -1.0), material);
+        hit.tangent = vector(1.0, 0.0, 0.0);
+        hit.uv = (0.5, 0.5);
+        hit
+    }
+
+    #[test]
+    fn bump_tilts_away_from_rising_height() {
+        let material = Material::default();
+        let hit = hit_on_front(&material);
+
+        let flat = BumpMap::new(|_| 0.5, 1.0);
+        assert_almost_eq_tuple(flat.shading_normal(&hit), hit.normalv);
+
+        // height rises toward +x, normal leans toward -x
+        let ramp = BumpMap::new(|p| p[0], 1.0);
+        let s = 1.0 / 2.0_f32.sqrt();
+        assert_almost_eq_tuple(ramp.shading_normal(&hit), vector(-s, 0.0, -s));
+    }
+
+    #[test]
+    fn noise_bump_stays_on_the_surface_side() {
+        let material = Material::default();
+        let mut hit = hit_on_front(&material);
+        let bump = BumpMap::from_noise(Perlin::new(0), 4.0, 0.05);
+        let mut tilted = 0;
+        for i in 0..50 {
+            hit.object_pos = point(i as f32 * 0.13, i as f32 * 0.07, 0.0);
+            let n = bump.shading_normal(&hit);
+            assert!((n.mag() - 1.0).abs() < 1e-4);
+            assert!(n.dot(hit.normalv) > 0.5);
+            if n.dot(hit.normalv) < 0.999 {
+                tilted += 1;
+            }
+        }
+        assert!(tilted > 25);
+    }
+
+    #[test]
+    fn normal_map_in_tangent_space() {
+        let material = Material::default();
+        let hit = hit_on_front(&material);
+
+        // (128, 128, 255) is the flat normal
+        let flat = image::RgbImage::from_raw(1, 1, vec![128, 128, 255]).unwrap();
+        let n = NormalMap::new(&flat).unwrap().shading_normal(&hit);
+        assert!(n.dot(hit.normalv) > 0.9999);
+
+        // pointing along the tangent (+u), half way up
+        let tilted = image::RgbImage::from_raw(1, 1, vec![255, 128, 255]).unwrap();
+        let n = NormalMap::new(&tilted).unwrap().shading_normal(&hit);
+        assert!(n[0] > 0.7 && n[2] < -0.7);
+    }
+}
@@ -1,5 +1,10 @@
 use crate::{Intersection, Ray};
 
 pub trait Object {
-    fn ray_intersect(&self, ray: &Ray) -> Vec<Intersection>;
+    fn ray_intersect(&self, ray: &Ray) -> Vec<Intersection<'_>>;
+
+    /// used to group statistics by object type
+    fn name(&self) -> &'static str {
+        std::any::type_name::<Self>()
+    }
 }
@@ -0,0 +1,181 @@
+use std::f32::consts::PI;
+
+use crate::Color;
+
+/// wavelength (nm) each rgb channel stands for when a wavelength dependent effect is
+/// evaluated without a spectrum: red, green, blue
+pub const RGB_WAVELENGTHS: [f32; 3] = [630.0, 532.0, 465.0];
+
+/// `color` with every channel but `channel` (0 = red, 1 = green, 2 = blue) set to 0
+pub fn keep_channel(color: Color, channel: usize) -> Color {
+    match channel {
+        0 => Color::new(color.red, 0.0, 0.0),
+        1 => Color::new(0.0, color.green, 0.0),
+        _ => Color::new(0.0, 0.0, color.blue),
+    }
+}
+
+/// refractive index that changes with the wavelength, so white light fans out into colors.
+/// coefficients use micrometers, as in glass catalogs
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Dispersion {
+    /// n = a + b / λ²
+    Cauchy { a: f32, b: f32 },
+    /// n² = 1 + Σ bᵢ λ² / (λ² - cᵢ)
+    Sellmeier { b: [f32; 3], c: [f32; 3] },
+}
+
+impl Dispersion {
+    /// common crown glass, n = 1.517 at 588 nm
+    pub const BK7: Dispersion = Dispersion::Sellmeier {
+        b: [1.039_612, 0.231_792_34, 1.010_469_5],
+        c: [0.006_000_699, 0.020_017_914, 103.560_65],
+    };
+
+    /// dense flint glass, n = 1.785 at 588 nm. about three times the spread of BK7
+    pub const SF11: Dispersion = Dispersion::Sellmeier {
+        b: [1.737_597, 0.313_747_35, 1.878_781],
+        c: [0.013_188_707, 0.062_306_814, 155.236_3],
+    };
+
+    /// n = 2.417 at 588 nm, the fire of a cut diamond
+    pub const DIAMOND: Dispersion = Dispersion::Sellmeier {
+        b: [0.3306, 4.3356, 0.0],
+        c: [0.030_625, 0.011_236, 0.0],
+    };
+
+    pub const WATER: Dispersion = Dispersion::Cauchy { a: 1.3242, b: 0.003_06 };
+
+    /// refractive index at `wavelength` nm
+    pub fn index_at(&self, wavelength: f32) -> f32 {
+        let um = wavelength / 1000.0;
+        let um2 = um * um;
+        match self {
+            Dispersion::Cauchy { a, b } => a + b / um2,
+            Dispersion::Sellmeier { b, c } => {
+                let sum: f32 = b.iter().zip(c).map(|(b, c)| b * um2 / (um2 - c)).sum();
+                (1.0 + sum).sqrt()
+            }
+        }
+    }
+}
+
+/// film a few hundred nanometers thick on a surface (soap bubble, oil on water). light reflected
+/// at its top and bottom interferes, so the reflectance depends on wavelength and angle
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ThinFilm {
+    /// nm
+    pub thickness: f32,
+    /// 1.33 for soapy water, 1.47 for oil
+    pub refractive_index: f32,
+}
+
+impl ThinFilm {
+    /// reflectance of unpolarized light at `wavelength` nm arriving with `cos_i` from a medium of
+    /// index `n1` onto the film, which lies on a medium of index `n3`
+    pub fn reflectance(&self, cos_i: f32, n1: f32, n3: f32, wavelength: f32) -> f32 {
+        let n2 = self.refractive_index;
+        let cos1 = cos_i.clamp(0.0, 1.0);
+        let sin2_1 = 1.0 - cos1 * cos1;
+
+        // snell's law into the film and on into the medium below it
+        let sin2_2 = (n1 / n2).powi(2) * sin2_1;
+        let sin2_3 = (n1 / n3).powi(2) * sin2_1;
+        if sin2_2 > 1.0 || sin2_3 > 1.0 {
+            // total internal reflection
+            return 1.0;
+        }
+        let cos2 = (1.0 - sin2_2).sqrt();
+        let cos3 = (1.0 - sin2_3).sqrt();
+
+        // fresnel amplitude coefficients at the top and the bottom of the film
+        let rs12 = (n1 * cos1 - n2 * cos2) / (n1 * cos1 + n2 * cos2);
+        let rs23 = (n2 * cos2 - n3 * cos3) / (n2 * cos2 + n3 * cos3);
+        let rp12 = (n2 * cos1 - n1 * cos2) / (n2 * cos1 + n1 * cos2);
+        let rp23 = (n3 * cos2 - n2 * cos3) / (n3 * cos2 + n2 * cos3);
+
+        // phase difference of one round trip through the film
+        let cos_delta = (4.0 * PI * n2 * self.thickness * cos2 / wavelength).cos();
+        let airy = |r12: f32, r23: f32| {
+            let cross = 2.0 * r12 * r23 * cos_delta;
+            (r12 * r12 + r23 * r23 + cross) / (1.0 + r12 * r12 * r23 * r23 + cross)
+        };
+
+        (airy(rs12, rs23) + airy(rp12, rp23)) / 2.0
+    }
+
+    /// `reflectance` at `wavelength`, or at each channel's `RGB_WAVELENGTHS` without one
+    pub fn reflectance_color(&self, cos_i: f32, n1: f32, n3: f32, wavelength: Option<f32>) -> Color {
+        match wavelength {
+            Some(wavelength) => {
+                let r = self.reflectance(cos_i, n1, n3, wavelength);
+                Color::new(r, r, r)
+            }
+            None => {
+                let [r, g, b] = RGB_WAVELENGTHS.map(|wavelength| self.reflectance(cos_i, n1, n3, wavelength));
+                Color::new(r, g, b)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::lib_test::assert_almost_eq_f32;
+
+    use super::*;
+
+    #[test]
+    fn glass_indices() {
+        assert!((Dispersion::BK7.index_at(587.6) - 1.5168).abs() < 1e-3);
+        assert!((Dispersion::SF11.index_at(587.6) - 1.7847).abs() < 1e-3);
+        assert!((Dispersion::DIAMOND.index_at(589.0) - 2.417).abs() < 2e-3);
+        assert!((Dispersion::WATER.index_at(589.0) - 1.333).abs() < 1e-3);
+
+        // blue bends more than red
+        for glass in [
+            Dispersion::BK7,
+            Dispersion::SF11,
+            Dispersion::DIAMOND,
+            Dispersion::WATER,
+        ] {
+            assert!(glass.index_at(RGB_WAVELENGTHS[2]) > glass.index_at(RGB_WAVELENGTHS[0]));
+        }
+    }
+
+    #[test]
+    fn film_without_thickness_is_bare_interface() {
+        let film = ThinFilm {
+            thickness: 0.0,
+            refractive_index: 1.33,
+        };
+        // ((1 - 1.5) / (1 + 1.5))²
+        assert_almost_eq_f32(film.reflectance(1.0, 1.0, 1.5, 550.0), 0.04);
+    }
+
+    #[test]
+    fn quarter_wave_coating_cancels_reflection() {
+        let index = 1.5_f32.sqrt();
+        let film = ThinFilm {
+            thickness: 550.0 / (4.0 * index),
+            refractive_index: index,
+        };
+        assert!(film.reflectance(1.0, 1.0, 1.5, 550.0) < 1e-4);
+        assert!(film.reflectance(1.0, 1.0, 1.5, 450.0) > 1e-3);
+    }
+
+    #[test]
+    fn soap_film_is_colored() {
+        let film = ThinFilm {
+            thickness: 300.0,
+            refractive_index: 1.33,
+        };
+        // 300 nm of soapy water looks green head on
+        let head_on = film.reflectance_color(1.0, 1.0, 1.0, None);
+        assert!(head_on.green > head_on.red + 0.02 && head_on.green > head_on.blue + 0.02);
+
+        // and shifts to blue at an angle, as the light path inside gets shorter
+        let oblique = film.reflectance_color(0.5, 1.0, 1.0, None);
+        assert!(oblique.blue > oblique.green + 0.05 && oblique.red < 0.01);
+    }
+}
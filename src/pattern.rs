@@ -0,0 +1,376 @@
+use std::fmt::Debug;
+
+use crate::{vector, Color, Matrix, Matrix4, Noise, Perlin, Uv, Vec4};
+
+/// color that varies over a surface. patterns are evaluated in object space
+/// (see `Intersection::object_pos`), then moved by the pattern's own transform,
+/// so a pattern scaled 2x has cells twice as big on every object it is put on
+pub trait Pattern: Debug {
+    /// inverse of the pattern transform (object space -> pattern space)
+    fn inv_transform(&self) -> &Matrix4;
+
+    fn set_inv_transform(&mut self, inv_transform: Matrix4);
+
+    /// color at a point in pattern space
+    fn pattern_at(&self, pos: Vec4) -> Color;
+
+    /// transform: pattern space -> object space.
+    /// None (and the pattern is left as it was) when `transform` has no inverse, e.g. a scale of 0
+    fn set_transform(&mut self, transform: Matrix4) -> Option<()> {
+        self.set_inv_transform(transform.inverse()?);
+        Some(())
+    }
+
+    /// color at a point in object space
+    fn color_at(&self, object_pos: Vec4) -> Color {
+        self.pattern_at(*self.inv_transform() * object_pos)
+    }
+
+    /// color at a surface point that also knows its uv. only textures care about the uv
+    fn color_at_uv(&self, object_pos: Vec4, _uv: Uv) -> Color {
+        self.color_at(object_pos)
+    }
+}
+
+/// a plain color, so colors can be used wherever a sub pattern is expected
+impl From<Color> for Box<dyn Pattern> {
+    fn from(color: Color) -> Self {
+        Box::new(SolidPattern::new(color))
+    }
+}
+
+impl<P: Pattern + 'static> From<P> for Box<dyn Pattern> {
+    fn from(pattern: P) -> Self {
+        Box::new(pattern)
+    }
+}
+
+fn is_even(v: f32) -> bool {
+    (v.floor() as i64).rem_euclid(2) == 0
+}
+
+/// the `Pattern` methods that get and set a pattern's `inv_transform: Matrix4` field
+macro_rules! inv_transform_accessors {
+    () => {
+        fn inv_transform(&self) -> &Matrix4 {
+            &self.inv_transform
+        }
+
+        fn set_inv_transform(&mut self, inv_transform: Matrix4) {
+            self.inv_transform = inv_transform;
+        }
+    };
+}
+
+pub(crate) use inv_transform_accessors;
+
+/// declares a pattern of two sub patterns `a` and `b` with its own transform, from its doc comment,
+/// name and `pattern_at` body. a and b are colors or patterns themselves, evaluated in this pattern's
+/// space and then their own transform
+macro_rules! pattern {
+    ($(#[$attr:meta])* $name:ident, |$self:ident, $pos:ident| $pattern_at:block) => {
+        $(#[$attr])*
+        #[derive(Debug)]
+        pub struct $name {
+            pub a: Box<dyn Pattern>,
+            pub b: Box<dyn Pattern>,
+            inv_transform: Matrix4,
+        }
+
+        impl $name {
+            pub fn new(a: impl Into<Box<dyn Pattern>>, b: impl Into<Box<dyn Pattern>>) -> Self {
+                Self {
+                    a: a.into(),
+                    b: b.into(),
+                    inv_transform: Matrix::identity(),
+                }
+            }
+        }
+
+        impl Pattern for $name {
+            inv_transform_accessors!();
+
+            fn pattern_at(&$self, $pos: Vec4) -> Color $pattern_at
+        }
+    };
+}
+
+/// same color everywhere
+#[derive(Debug)]
+pub struct SolidPattern {
+    pub color: Color,
+    inv_transform: Matrix4,
+}
+
+impl SolidPattern {
+    pub fn new(color: Color) -> Self {
+        Self {
+            color,
+            inv_transform: Matrix::identity(),
+        }
+    }
+}
+
+impl Pattern for SolidPattern {
+    inv_transform_accessors!();
+
+    fn pattern_at(&self, _pos: Vec4) -> Color {
+        self.color
+    }
+}
+
+pattern! {
+    /// alternating a, b bands along x, 1 unit wide. a and b can be patterns, so stripes of checkers work
+    StripePattern, |self, pos| {
+        if is_even(pos[0]) {
+            self.a.color_at(pos)
+        } else {
+            self.b.color_at(pos)
+        }
+    }
+}
+
+pattern! {
+    /// blends from a to b along x, repeating every unit
+    GradientPattern, |self, pos| {
+        let fraction = pos[0] - pos[0].floor();
+        let a = self.a.color_at(pos);
+        a + (self.b.color_at(pos) - a) * fraction
+    }
+}
+
+pattern! {
+    /// concentric a, b rings around the y axis
+    RingPattern, |self, pos| {
+        let dist = (pos[0] * pos[0] + pos[2] * pos[2]).sqrt();
+        if is_even(dist) {
+            self.a.color_at(pos)
+        } else {
+            self.b.color_at(pos)
+        }
+    }
+}
+
+pattern! {
+    /// 3d checker of unit cubes
+    CheckerPattern, |self, pos| {
+        if is_even(pos[0].floor() + pos[1].floor() + pos[2].floor()) {
+            self.a.color_at(pos)
+        } else {
+            self.b.color_at(pos)
+        }
+    }
+}
+
+pattern! {
+    /// 2d checker of unit squares on the xz plane, constant along y
+    Checker2dPattern, |self, pos| {
+        if is_even(pos[0].floor() + pos[2].floor()) {
+            self.a.color_at(pos)
+        } else {
+            self.b.color_at(pos)
+        }
+    }
+}
+
+pattern! {
+    /// blends from a to b with the distance from the y axis, repeating every unit
+    RadialGradientPattern, |self, pos| {
+        let dist = (pos[0] * pos[0] + pos[2] * pos[2]).sqrt();
+        let fraction = dist - dist.floor();
+        let a = self.a.color_at(pos);
+        a + (self.b.color_at(pos) - a) * fraction
+    }
+}
+
+/// mix of two patterns, `weight` 0 = all a, 1 = all b
+#[derive(Debug)]
+pub struct BlendPattern {
+    pub a: Box<dyn Pattern>,
+    pub b: Box<dyn Pattern>,
+    pub weight: f32,
+    inv_transform: Matrix4,
+}
+
+impl BlendPattern {
+    pub fn new(a: impl Into<Box<dyn Pattern>>, b: impl Into<Box<dyn Pattern>>) -> Self {
+        Self {
+            a: a.into(),
+            b: b.into(),
+            weight: 0.5,
+            inv_transform: Matrix::identity(),
+        }
+    }
+}
+
+impl Pattern for BlendPattern {
+    inv_transform_accessors!();
+
+    fn pattern_at(&self, pos: Vec4) -> Color {
+        self.a.color_at(pos) * (1.0 - self.weight) + self.b.color_at(pos) * self.weight
+    }
+}
+
+/// jitters the lookup point of the inner pattern with noise.
+/// perturbed stripes look like marble, perturbed rings like wood
+#[derive(Debug)]
+pub struct PerturbedPattern {
+    pub pattern: Box<dyn Pattern>,
+    /// how far (in pattern space) a point can be moved
+    pub amount: f32,
+    /// perlin with seed 0 by default
+    pub noise: Box<dyn Noise>,
+    inv_transform: Matrix4,
+}
+
+impl PerturbedPattern {
+    pub fn new(pattern: impl Into<Box<dyn Pattern>>, amount: f32) -> Self {
+        Self {
+            pattern: pattern.into(),
+            amount,
+            noise: Box::new(Perlin::new(0)),
+            inv_transform: Matrix::identity(),
+        }
+    }
+}
+
+impl Pattern for PerturbedPattern {
+    inv_transform_accessors!();
+
+    fn pattern_at(&self, pos: Vec4) -> Color {
+        // sample the noise at far apart offsets so the three axes move independently
+        let dx = self.noise.noise(pos);
+        let dy = self.noise.noise(pos + vector(31.4, 47.2, 11.9));
+        let dz = self.noise.noise(pos + vector(-23.7, 5.3, 67.1));
+        self.pattern.color_at(pos + vector(dx, dy, dz) * self.amount)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{lib_test::assert_almost_eq_color, point, Angle};
+
+    use super::*;
+
+    const WHITE: Color = Color::WHITE;
+    const BLACK: Color = Color::BLACK;
+
+    #[test]
+    fn stripe_alternates_in_x() {
+        let pattern = StripePattern::new(WHITE, BLACK);
+        assert_almost_eq_color(pattern.pattern_at(point(0.0, 0.0, 0.0)), WHITE);
+        assert_almost_eq_color(pattern.pattern_at(point(0.0, 1.0, 0.0)), WHITE);
+        assert_almost_eq_color(pattern.pattern_at(point(0.0, 0.0, 2.0)), WHITE);
+        assert_almost_eq_color(pattern.pattern_at(point(0.9, 0.0, 0.0)), WHITE);
+        assert_almost_eq_color(pattern.pattern_at(point(1.0, 0.0, 0.0)), BLACK);
+        assert_almost_eq_color(pattern.pattern_at(point(-0.1, 0.0, 0.0)), BLACK);
+        assert_almost_eq_color(pattern.pattern_at(point(-1.0, 0.0, 0.0)), BLACK);
+        assert_almost_eq_color(pattern.pattern_at(point(-1.1, 0.0, 0.0)), WHITE);
+    }
+
+    #[test]
+    fn gradient_interpolates() {
+        let pattern = GradientPattern::new(WHITE, BLACK);
+        assert_almost_eq_color(pattern.pattern_at(point(0.0, 0.0, 0.0)), WHITE);
+        assert_almost_eq_color(pattern.pattern_at(point(0.25, 0.0, 0.0)), Color::new(0.75, 0.75, 0.75));
+        assert_almost_eq_color(pattern.pattern_at(point(0.5, 0.0, 0.0)), Color::new(0.5, 0.5, 0.5));
+        assert_almost_eq_color(pattern.pattern_at(point(0.75, 0.0, 0.0)), Color::new(0.25, 0.25, 0.25));
+    }
+
+    #[test]
+    fn ring_extends_in_x_and_z() {
+        let pattern = RingPattern::new(WHITE, BLACK);
+        assert_almost_eq_color(pattern.pattern_at(point(0.0, 0.0, 0.0)), WHITE);
+        assert_almost_eq_color(pattern.pattern_at(point(1.0, 0.0, 0.0)), BLACK);
+        assert_almost_eq_color(pattern.pattern_at(point(0.0, 0.0, 1.0)), BLACK);
+        assert_almost_eq_color(pattern.pattern_at(point(0.708, 0.0, 0.708)), BLACK);
+    }
+
+    #[test]
+    fn checker_repeats_in_all_axes() {
+        let pattern = CheckerPattern::new(WHITE, BLACK);
+        assert_almost_eq_color(pattern.pattern_at(point(0.0, 0.0, 0.0)), WHITE);
+        assert_almost_eq_color(pattern.pattern_at(point(0.99, 0.0, 0.0)), WHITE);
+        assert_almost_eq_color(pattern.pattern_at(point(1.01, 0.0, 0.0)), BLACK);
+        assert_almost_eq_color(pattern.pattern_at(point(0.0, 1.01, 0.0)), BLACK);
+        assert_almost_eq_color(pattern.pattern_at(point(0.0, 0.0, 1.01)), BLACK);
+        assert_almost_eq_color(pattern.pattern_at(point(-0.5, -0.5, 0.5)), WHITE);
+    }
+
+    #[test]
+    fn checker_2d_ignores_y() {
+        let pattern = Checker2dPattern::new(WHITE, BLACK);
+        assert_almost_eq_color(pattern.pattern_at(point(0.5, 0.0, 0.5)), WHITE);
+        assert_almost_eq_color(pattern.pattern_at(point(0.5, 7.3, 0.5)), WHITE);
+        assert_almost_eq_color(pattern.pattern_at(point(1.5, 0.0, 0.5)), BLACK);
+        assert_almost_eq_color(pattern.pattern_at(point(-0.5, 0.0, 0.5)), BLACK);
+    }
+
+    #[test]
+    fn radial_gradient_follows_distance() {
+        let pattern = RadialGradientPattern::new(WHITE, BLACK);
+        assert_almost_eq_color(pattern.pattern_at(point(0.0, 0.0, 0.0)), WHITE);
+        assert_almost_eq_color(pattern.pattern_at(point(0.0, 0.0, 0.5)), Color::new(0.5, 0.5, 0.5));
+        assert_almost_eq_color(pattern.pattern_at(point(0.3, 5.0, 0.4)), Color::new(0.5, 0.5, 0.5));
+    }
+
+    #[test]
+    fn pattern_transform_scales_cells() {
+        let mut pattern = StripePattern::new(WHITE, BLACK);
+        pattern.set_transform(Matrix::scale(point(2.0, 2.0, 2.0))).unwrap();
+        assert_almost_eq_color(pattern.color_at(point(1.5, 0.0, 0.0)), WHITE);
+        assert_almost_eq_color(pattern.color_at(point(2.5, 0.0, 0.0)), BLACK);
+    }
+
+    #[test]
+    fn pattern_transform_rotates() {
+        // stripes along x become stripes along z after a quarter turn around y
+        let mut pattern = StripePattern::new(WHITE, BLACK);
+        pattern
+            .set_transform(Matrix4::rotation_y(Angle::from_degree(90.0)))
+            .unwrap();
+        assert_almost_eq_color(pattern.color_at(point(5.0, 0.0, -0.5)), WHITE);
+        assert_almost_eq_color(pattern.color_at(point(5.0, 0.0, 0.5)), BLACK);
+    }
+
+    #[test]
+    fn checker_of_stripes() {
+        let mut stripes = StripePattern::new(WHITE, BLACK);
+        stripes.set_transform(Matrix::scale(point(0.25, 0.25, 0.25))).unwrap();
+        let pattern = CheckerPattern::new(stripes, Color::new(0.5, 0.5, 0.5));
+
+        // first cell holds the stripes, quarter unit wide
+        assert_almost_eq_color(pattern.color_at(point(0.1, 0.5, 0.5)), WHITE);
+        assert_almost_eq_color(pattern.color_at(point(0.3, 0.5, 0.5)), BLACK);
+        assert_almost_eq_color(pattern.color_at(point(0.6, 0.5, 0.5)), WHITE);
+        // neighbouring cell is solid
+        assert_almost_eq_color(pattern.color_at(point(1.3, 0.5, 0.5)), Color::new(0.5, 0.5, 0.5));
+    }
+
+    #[test]
+    fn blend_mixes_patterns() {
+        let stripes = StripePattern::new(WHITE, BLACK);
+        let mut blend = BlendPattern::new(stripes, Color::new(1.0, 0.0, 0.0));
+        assert_almost_eq_color(blend.color_at(point(0.5, 0.0, 0.0)), Color::new(1.0, 0.5, 0.5));
+        assert_almost_eq_color(blend.color_at(point(1.5, 0.0, 0.0)), Color::new(0.5, 0.0, 0.0));
+
+        blend.weight = 0.0;
+        assert_almost_eq_color(blend.color_at(point(1.5, 0.0, 0.0)), BLACK);
+    }
+
+    #[test]
+    fn perturbed_moves_lookup() {
+        // noise is 0 on lattice points, so those are left alone
+        let pattern = PerturbedPattern::new(GradientPattern::new(WHITE, BLACK), 0.3);
+        assert_almost_eq_color(pattern.color_at(point(0.0, 0.0, 0.0)), WHITE);
+
+        let moved = (0..20)
+            .map(|i| point(i as f32 * 0.37 + 0.1, 0.5, 0.5))
+            .filter(|&p| {
+                let plain = GradientPattern::new(WHITE, BLACK).color_at(p);
+                (pattern.color_at(p) - plain).luminance().abs() > 0.01
+            })
+            .count();
+        assert!(moved > 10);
+    }
+}
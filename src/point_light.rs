@@ -1,58 +1,168 @@
-use crate::{Color, Material, Vec4};
+use std::f32::consts::PI;
+
+use crate::{spectral_intensity, Angle, Color, Light, LightSample, Material, ShadingFrame, Spectrum, Vec4};
 
 pub struct PointLight {
     pub pos: Vec4,
     pub intensity: Color,
+    /// emission for spectral rendering (a `Blackbody`, a measured lamp, ...). `intensity` is upsampled when None
+    pub spectrum: Option<Box<dyn Spectrum>>,
 }
 
 impl PointLight {
     pub fn new(pos: Vec4, intensity: Color) -> Self {
-        Self { pos, intensity }
+        Self {
+            pos,
+            intensity,
+            spectrum: None,
+        }
+    }
+}
+
+impl Light for PointLight {
+    fn illuminate(&self, pos: Vec4, wavelength: Option<f32>) -> LightSample {
+        let to_light = self.pos - pos;
+        LightSample {
+            lightv: to_light.normalize(),
+            distance: to_light.mag(),
+            intensity: spectral_intensity(self.intensity, self.spectrum.as_deref(), wavelength),
+        }
+    }
+}
+
+/// a point light that only shines inside a cone around `direction`. full intensity within `inner`
+/// of the axis, fading smoothly to nothing at `outer`
+pub struct SpotLight {
+    pub light: PointLight,
+    /// axis of the cone, the way the light points
+    pub direction: Vec4,
+    pub inner: Angle,
+    pub outer: Angle,
+}
+
+impl SpotLight {
+    /// `direction` need not be unit length, but must not be zero
+    pub fn new(pos: Vec4, direction: Vec4, inner: Angle, outer: Angle, intensity: Color) -> Self {
+        Self {
+            light: PointLight::new(pos, intensity),
+            direction: direction.normalize(),
+            inner,
+            outer,
+        }
+    }
+
+    /// share of the intensity reaching a point `lightv` sees the light from. 1 inside the inner cone,
+    /// 0 outside the outer one
+    pub fn falloff(&self, lightv: Vec4) -> f32 {
+        let cos = -lightv.dot(self.direction.normalize());
+        let cos_outer = self.outer.radian().cos();
+        let cos_inner = self.inner.radian().cos();
+        if cos_inner <= cos_outer {
+            // no fade: a hard edged cone
+            return if cos >= cos_outer { 1.0 } else { 0.0 };
+        }
+
+        // smoothstep
+        let t = ((cos - cos_outer) / (cos_inner - cos_outer)).clamp(0.0, 1.0);
+        t * t * (3.0 - 2.0 * t)
+    }
+}
+
+impl Light for SpotLight {
+    fn illuminate(&self, pos: Vec4, wavelength: Option<f32>) -> LightSample {
+        let sample = self.light.illuminate(pos, wavelength);
+        LightSample {
+            intensity: sample.intensity * self.falloff(sample.lightv),
+            ..sample
+        }
     }
 }
 
+/// diffuse + specular from one light (see `Light::illuminate`). `color`: surface color at the point
+/// (material color or pattern)
 pub fn point_lighting(
     material: &Material,
-    light: &PointLight,
-    position: Vec4,
+    color: Color,
+    light: &LightSample,
     eyev: Vec4,
-    normalv: Vec4,
+    frame: &ShadingFrame,
     is_shadowed: bool,
 ) -> Color {
     if is_shadowed {
         return Color::BLACK;
     }
 
-    let color = material.color(position);
-    let effective_color = color * light.intensity;
-    let lightv = (light.pos - position).normalize();
+    let normalv = frame.normalv;
+    let lightv = light.lightv;
     let light_dot_normal = lightv.dot(normalv);
-
-    let diffuse;
-    let specular;
     if light_dot_normal < 0.0 {
-        diffuse = Color::BLACK;
-        specular = Color::BLACK;
-    } else {
-        diffuse = effective_color * material.diffuse * light_dot_normal;
-
-        let reflectv = (-lightv).reflect(normalv);
-        let reflect_dot_eye = reflectv.dot(-eyev);
-        if reflect_dot_eye <= 0.0 {
-            specular = Color::BLACK;
-        } else {
-            let factor = f32::powf(reflect_dot_eye, material.shininess);
-            specular = light.intensity * material.specular * factor;
-        }
+        return Color::BLACK;
     }
 
-    diffuse + specular
+    // a point light of intensity 1 lights a white lambert surface (brdf 1 / pi) facing it with 1
+    let base = material.bsdf.evaluate(material, color, frame, lightv, -eyev) * light.intensity * PI;
+
+    match &material.clearcoat {
+        Some(coat) => coat.layer(base, light.intensity, normalv, lightv, -eyev),
+        None => base,
+    }
+}
+
+/// oren-nayar diffuse relative to lambert (multiply with n.l). 1 when `sigma` is 0.
+/// rough surfaces are flatter: darker facing the light, brighter back toward it
+pub fn oren_nayar(normalv: Vec4, lightv: Vec4, viewv: Vec4, sigma: f32) -> f32 {
+    if sigma <= 0.0 {
+        return 1.0;
+    }
+
+    let s2 = sigma * sigma;
+    let a = 1.0 - 0.5 * s2 / (s2 + 0.33);
+    let b = 0.45 * s2 / (s2 + 0.09);
+
+    let cos_i = normalv.dot(lightv).clamp(0.0, 1.0);
+    let cos_r = normalv.dot(viewv).clamp(1e-4, 1.0);
+    let sin_i = (1.0 - cos_i * cos_i).sqrt();
+    let sin_r = (1.0 - cos_r * cos_r).sqrt();
+
+    // cos of the azimuth between light and eye, both projected on the surface
+    let light_tangent = lightv - normalv * cos_i;
+    let view_tangent = viewv - normalv * cos_r;
+    let cos_phi = if light_tangent.mag() > 1e-4 && view_tangent.mag() > 1e-4 {
+        light_tangent.normalize().dot(view_tangent.normalize()).max(0.0)
+    } else {
+        0.0
+    };
+
+    // sin(max(theta_i, theta_r)) * tan(min(theta_i, theta_r))
+    let (sin_alpha, tan_beta) = if cos_i < cos_r {
+        (sin_i, sin_r / cos_r)
+    } else {
+        (sin_r, sin_i / cos_i.max(1e-4))
+    };
+
+    a + b * cos_phi * sin_alpha * tan_beta
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::{lib_test::assert_almost_eq_color, point, vector};
+    use crate::{
+        lib_test::{assert_almost_eq_color, assert_almost_eq_f32},
+        point, vector, Microfacet,
+    };
+
+    /// `point_lighting` of the material's own color, lit by `light` at `pos`
+    fn lighting(material: &Material, light: &dyn Light, pos: Vec4, eyev: Vec4, normalv: Vec4, shadow: bool) -> Color {
+        let sample = light.illuminate(pos, None);
+        point_lighting(
+            material,
+            material.color,
+            &sample,
+            eyev,
+            &ShadingFrame::from_normal(normalv),
+            shadow,
+        )
+    }
 
     #[test]
     fn lighting_with_the_eye_between_light_and_surface() {
@@ -61,8 +171,9 @@ mod tests {
         let position = point(0.0, 0.0, 0.0);
         let light = PointLight::new(point(0.0, 0.0, -10.0), Color::WHITE);
         let material = Material::default();
-        let result = point_lighting(&material, &light, position, eyev, normalv, false);
-        assert_almost_eq_color(result, Color::new(1.9, 1.9, 1.9));
+        let result = lighting(&material, &light, position, eyev, normalv, false);
+        // ambient is added once per hit by World::shade, not per light
+        assert_almost_eq_color(result, Color::new(1.8, 1.8, 1.8));
     }
 
     #[test]
@@ -72,8 +183,8 @@ mod tests {
         let position = point(0.0, 0.0, 0.0);
         let light = PointLight::new(point(0.0, 0.0, -10.0), Color::WHITE);
         let material = Material::default();
-        let result = point_lighting(&material, &light, position, eyev, normalv, true);
-        assert_almost_eq_color(result, Color::new(0.1, 0.1, 0.1));
+        let result = lighting(&material, &light, position, eyev, normalv, true);
+        assert_almost_eq_color(result, Color::BLACK);
     }
 
     #[test]
@@ -83,7 +194,149 @@ mod tests {
         let position = point(0.0, 0.0, 0.0);
         let light = PointLight::new(point(0.0, 0.0, -10.0), Color::WHITE);
         let material = Material::default();
-        let result = point_lighting(&material, &light, position, eyev, normalv, false);
-        assert_almost_eq_color(result, Color::new(1.0, 1.0, 1.0));
+        let result = lighting(&material, &light, position, eyev, normalv, false);
+        assert_almost_eq_color(result, Color::new(0.9, 0.9, 0.9));
+    }
+
+    #[test]
+    fn microfacet_rough_dielectric_close_to_lambert() {
+        let eyev = vector(0.0, 0.0, 1.0);
+        let normalv = vector(0.0, 0.0, -1.0);
+        let position = point(0.0, 0.0, 0.0);
+        let light = PointLight::new(point(0.0, -10.0, -10.0), Color::WHITE);
+        let material = Material {
+            bsdf: Box::new(Microfacet {
+                metallic: 0.0,
+                roughness: 1.0,
+            }),
+            ..Default::default()
+        };
+        let result = lighting(&material, &light, position, eyev, normalv, false);
+        // lambert with diffuse = 1 gives cos 45 = 0.707, minus what the specular lobe takes
+        assert!(result.red > 0.6 && result.red < 0.75, "{:?}", result);
+    }
+
+    #[test]
+    fn microfacet_light_behind_surface() {
+        let material = Material {
+            bsdf: Box::new(Microfacet {
+                metallic: 1.0,
+                roughness: 0.5,
+            }),
+            ..Default::default()
+        };
+        let light = PointLight::new(point(0.0, 0.0, 10.0), Color::WHITE);
+        let eyev = vector(0.0, 0.0, 1.0);
+        let normalv = vector(0.0, 0.0, -1.0);
+        let result = lighting(&material, &light, point(0.0, 0.0, 0.0), eyev, normalv, false);
+        assert_almost_eq_color(result, Color::BLACK);
+    }
+
+    #[test]
+    fn oren_nayar_at_zero_roughness_is_lambert() {
+        let normalv = vector(0.0, 1.0, 0.0);
+        let material = Material {
+            specular: 0.0,
+            ..Default::default()
+        };
+        let light = PointLight::new(point(3.0, 4.0, -2.0), Color::WHITE);
+        let position = point(0.0, 0.0, 0.0);
+        for eyev in [
+            vector(0.0, -1.0, 0.0),
+            vector(0.6, -0.8, 0.0),
+            vector(-0.3, -0.2, 0.9).normalize(),
+        ] {
+            let lambert = material.diffuse * (light.pos - position).normalize().dot(normalv);
+            let result = lighting(&material, &light, position, eyev, normalv, false);
+            assert_almost_eq_color(result, Color::new(lambert, lambert, lambert));
+        }
+        assert_almost_eq_f32(
+            oren_nayar(normalv, vector(0.6, 0.8, 0.0), vector(0.0, 1.0, 0.0), 0.0),
+            1.0,
+        );
+    }
+
+    #[test]
+    fn oren_nayar_reference_values() {
+        let normalv = vector(0.0, 1.0, 0.0);
+        let sigma = 0.5;
+        let a = 1.0 - 0.5 * 0.25 / (0.25 + 0.33);
+        let b = 0.45 * 0.25 / (0.25 + 0.09);
+
+        // light and eye straight above: only the A term
+        let up = vector(0.0, 1.0, 0.0);
+        assert_almost_eq_f32(oren_nayar(normalv, up, up, sigma), a);
+
+        // light 60 degrees, eye 30 degrees, same azimuth: A + B sin60 tan30
+        let light = vector(60f32.to_radians().sin(), 60f32.to_radians().cos(), 0.0);
+        let eye = vector(30f32.to_radians().sin(), 30f32.to_radians().cos(), 0.0);
+        let expected = a + b * 60f32.to_radians().sin() * 30f32.to_radians().tan();
+        assert_almost_eq_f32(oren_nayar(normalv, light, eye, sigma), expected);
+
+        // opposite azimuth: cos(phi) clamps to 0
+        let eye = vector(-eye[0], eye[1], 0.0);
+        assert_almost_eq_f32(oren_nayar(normalv, light, eye, sigma), a);
+    }
+
+    #[test]
+    fn rough_diffuse_is_darker_head_on() {
+        let eyev = vector(0.0, 0.0, 1.0);
+        let normalv = vector(0.0, 0.0, -1.0);
+        let light = PointLight::new(point(0.0, 0.0, -10.0), Color::WHITE);
+        let mut material = Material {
+            specular: 0.0,
+            ..Default::default()
+        };
+        let smooth = lighting(&material, &light, point(0.0, 0.0, 0.0), eyev, normalv, false);
+        material.diffuse_roughness = 0.5;
+        let rough = lighting(&material, &light, point(0.0, 0.0, 0.0), eyev, normalv, false);
+        assert!(rough.red < smooth.red);
+    }
+
+    fn spot() -> SpotLight {
+        SpotLight::new(
+            point(0.0, 10.0, 0.0),
+            vector(0.0, -1.0, 0.0),
+            Angle::from_degree(20.0),
+            Angle::from_degree(40.0),
+            Color::WHITE,
+        )
+    }
+
+    #[test]
+    fn spot_light_cone() {
+        let spot = spot();
+        let at_angle = |degree: f32| {
+            let x = 10.0 * degree.to_radians().tan();
+            spot.illuminate(point(x, 0.0, 0.0), None).intensity.red
+        };
+        assert_eq!(at_angle(0.0), 1.0);
+        assert_eq!(at_angle(19.0), 1.0);
+        assert_eq!(at_angle(41.0), 0.0);
+        assert_eq!(at_angle(80.0), 0.0);
+
+        // fades smoothly and steadily in between
+        let fade: Vec<f32> = (21..40).map(|degree| at_angle(degree as f32)).collect();
+        assert!(fade.windows(2).all(|w| w[0] > w[1]));
+        assert!(fade[0] > 0.95 && fade[18] < 0.05);
+    }
+
+    #[test]
+    fn spot_light_is_a_point_light_inside_the_cone() {
+        let spot = spot();
+        let pos = point(1.0, 0.0, 1.0);
+        let sample = spot.illuminate(pos, None);
+        let point_sample = spot.light.illuminate(pos, None);
+        assert_almost_eq_f32(sample.distance, point_sample.distance);
+        assert_almost_eq_color(sample.intensity, point_sample.intensity);
+
+        // a cone that does not fade has a hard edge
+        let hard = SpotLight {
+            inner: Angle::from_degree(30.0),
+            outer: Angle::from_degree(30.0),
+            ..spot
+        };
+        assert_eq!(hard.falloff(vector(-0.49, 0.87, 0.0).normalize()), 1.0);
+        assert_eq!(hard.falloff(vector(-0.51, 0.86, 0.0).normalize()), 0.0);
     }
 }
@@ -0,0 +1,206 @@
+use std::time::{Duration, Instant};
+
+use crate::{render_sample, Camera, CancelToken, Canvas, Color, RenderStatus, Rng, World};
+
+/// when to stop refining. a limit of None never stops on its own
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ProgressiveLimits {
+    pub time_budget: Option<Duration>,
+    pub max_samples: Option<u32>,
+}
+
+/// keeps refining an image by adding one sample per pixel on every pass.
+/// first pass samples pixel centers (same image as `render`), later passes jitter inside the pixel
+pub struct ProgressiveRenderer<'a> {
+    world: &'a World,
+    camera: &'a Camera,
+    accum: Vec<Color>,
+    samples: u32,
+    rng: Rng,
+    /// pixels covered by `World::outline`. they do not change between passes, so they are found once
+    outline_edges: Vec<(u32, u32)>,
+}
+
+impl<'a> ProgressiveRenderer<'a> {
+    pub fn new(world: &'a World, camera: &'a Camera) -> Self {
+        let pixel_count = camera.hsize as usize * camera.vsize as usize;
+        let outline_edges = match &world.outline {
+            Some(outline) => outline.edges(world, camera, 0..camera.hsize, 0..camera.vsize),
+            None => Vec::new(),
+        };
+        Self {
+            world,
+            camera,
+            accum: vec![Color::BLACK; pixel_count],
+            samples: 0,
+            rng: Rng::new(0),
+            outline_edges,
+        }
+    }
+
+    /// samples per pixel accumulated so far
+    pub fn samples(&self) -> u32 {
+        self.samples
+    }
+
+    pub fn render_pass(&mut self) {
+        let jitter = if self.samples == 0 { 0.0 } else { 1.0 };
+
+        for iy in 0..self.camera.vsize {
+            for ix in 0..self.camera.hsize {
+                let x = ix as f32 + (self.rng.next_f32() - 0.5) * jitter;
+                let y = iy as f32 + (self.rng.next_f32() - 0.5) * jitter;
+                let color = render_sample(self.world, self.camera, x, y);
+                self.accum[(ix + iy * self.camera.hsize) as usize] += color;
+            }
+        }
+
+        self.samples += 1;
+    }
+
+    /// writes the current average into `canvas`
+    pub fn write_canvas(&self, canvas: &mut Canvas) {
+        assert_eq!(canvas.get_size(), (self.camera.hsize, self.camera.vsize));
+
+        let inv_samples = if self.samples == 0 {
+            0.0
+        } else {
+            1.0 / self.samples as f32
+        };
+
+        for iy in 0..self.camera.vsize {
+            for ix in 0..self.camera.hsize {
+                let sum = self.accum[(ix + iy * self.camera.hsize) as usize];
+                canvas.write_pixel(ix, iy, sum * inv_samples);
+            }
+        }
+
+        if let Some(outline) = &self.world.outline {
+            for &(x, y) in &self.outline_edges {
+                canvas.write_pixel(x, y, outline.color);
+            }
+        }
+    }
+
+    pub fn canvas(&self, gamma: Option<f32>) -> Canvas {
+        let mut canvas = Canvas::new(self.camera.hsize, self.camera.vsize, gamma);
+        self.write_canvas(&mut canvas);
+        canvas
+    }
+
+    /// renders passes until a limit is reached or `cancel` is set.
+    /// at least one pass is always rendered so there is a usable image.
+    /// `on_pass` is called after every pass, e.g. to save an intermediate image
+    pub fn run<F>(&mut self, limits: ProgressiveLimits, cancel: &CancelToken, mut on_pass: F) -> RenderStatus
+    where
+        F: FnMut(&Self),
+    {
+        let start = Instant::now();
+
+        loop {
+            if cancel.is_cancelled() {
+                return RenderStatus::Cancelled;
+            }
+
+            self.render_pass();
+            on_pass(self);
+
+            if let Some(max_samples) = limits.max_samples {
+                if self.samples >= max_samples {
+                    return RenderStatus::Completed;
+                }
+            }
+
+            if let Some(time_budget) = limits.time_budget {
+                // stop if the next pass would likely overrun the budget
+                let elapsed = start.elapsed();
+                let per_pass = elapsed / self.samples;
+                if elapsed + per_pass > time_budget {
+                    return RenderStatus::Completed;
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{lib_test::assert_almost_eq_color, point, render, vector, view_transform, Angle};
+
+    use super::*;
+
+    fn test_camera() -> Camera {
+        let view_mat = view_transform(point(0.0, 0.0, -5.0), point(0.0, 0.0, 0.0), vector(0.0, 1.0, 0.0));
+        Camera::new(9, 9, Angle::from_degree(60.0), 1.0, view_mat)
+    }
+
+    #[test]
+    fn first_pass_matches_render() {
+        let world = World::default();
+        let camera = test_camera();
+
+        let mut expected = Canvas::new(9, 9, None);
+        render(&world, &camera, &mut expected);
+
+        let mut progressive = ProgressiveRenderer::new(&world, &camera);
+        progressive.render_pass();
+        let canvas = progressive.canvas(None);
+
+        for x in 0..9 {
+            for y in 0..9 {
+                assert_almost_eq_color(canvas.pixel_at(x, y), expected.pixel_at(x, y));
+            }
+        }
+    }
+
+    #[test]
+    fn stops_at_max_samples() {
+        let world = World::default();
+        let camera = test_camera();
+        let mut progressive = ProgressiveRenderer::new(&world, &camera);
+
+        let limits = ProgressiveLimits {
+            max_samples: Some(4),
+            ..Default::default()
+        };
+        let mut passes = Vec::new();
+        let status = progressive.run(limits, &CancelToken::new(), |r| passes.push(r.samples()));
+
+        assert_eq!(status, RenderStatus::Completed);
+        assert_eq!(passes, vec![1, 2, 3, 4]);
+    }
+
+    #[test]
+    fn zero_time_budget_renders_one_pass() {
+        let world = World::default();
+        let camera = test_camera();
+        let mut progressive = ProgressiveRenderer::new(&world, &camera);
+
+        let limits = ProgressiveLimits {
+            time_budget: Some(Duration::from_secs(0)),
+            ..Default::default()
+        };
+        progressive.run(limits, &CancelToken::new(), |_| {});
+
+        assert_eq!(progressive.samples(), 1);
+    }
+
+    #[test]
+    fn jittered_samples_average_flat_color() {
+        // camera inside a sphere sees the same color at every sample
+        let mut world = World::new();
+        let mut sphere = crate::Sphere::new(point(0.0, 0.0, -5.0), 100.0);
+        sphere.mat.diffuse = 0.0;
+        sphere.mat.specular = 0.0;
+        world.add_object(sphere);
+
+        let camera = test_camera();
+        let mut progressive = ProgressiveRenderer::new(&world, &camera);
+        for _ in 0..3 {
+            progressive.render_pass();
+        }
+
+        let canvas = progressive.canvas(None);
+        assert_almost_eq_color(canvas.pixel_at(4, 4), Color::WHITE * 0.1);
+    }
+}
@@ -4,11 +4,35 @@ use crate::{Aabb, Sphere, Tuple, Vec4};
 pub struct Ray {
     pub origin: Vec4,
     pub dir: Vec4,
+    /// nm. set on rays that only carry a single wavelength (see `Dispersion`), none for plain rgb
+    pub wavelength: Option<f32>,
 }
 
 impl Ray {
     pub fn new(origin: Vec4, dir: Vec4) -> Self {
-        Self { origin, dir }
+        Self {
+            origin,
+            dir,
+            wavelength: None,
+        }
+    }
+
+    /// reflected / refracted continuation of this ray. keeps its wavelength
+    pub fn spawn(&self, origin: Vec4, dir: Vec4) -> Self {
+        Self {
+            origin,
+            dir,
+            wavelength: self.wavelength,
+        }
+    }
+
+    /// the same ray carrying only `wavelength` nm
+    pub fn at_wavelength(&self, wavelength: f32) -> Self {
+        Self {
+            origin: self.origin,
+            dir: self.dir,
+            wavelength: Some(wavelength),
+        }
     }
 
     pub fn position(&self, t: f32) -> Vec4 {
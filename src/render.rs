@@ -0,0 +1,239 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use crate::{Camera, Canvas, Color, RenderStats, World};
+
+/// shared flag to stop a running render from another thread (GUI, ctrl-c handler, ...)
+#[derive(Debug, Clone, Default)]
+pub struct CancelToken(Arc<AtomicBool>);
+
+impl CancelToken {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::Relaxed)
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct RenderProgress {
+    pub rows_done: u32,
+    pub total_rows: u32,
+    pub rays: u64,
+    pub elapsed: Duration,
+}
+
+impl RenderProgress {
+    /// 0 to 1
+    pub fn fraction(&self) -> f32 {
+        if self.total_rows == 0 {
+            1.0
+        } else {
+            self.rows_done as f32 / self.total_rows as f32
+        }
+    }
+
+    pub fn rays_per_sec(&self) -> f32 {
+        let secs = self.elapsed.as_secs_f32();
+        if secs <= 0.0 {
+            0.0
+        } else {
+            self.rays as f32 / secs
+        }
+    }
+
+    /// estimated remaining time. None until the first row is done
+    pub fn eta(&self) -> Option<Duration> {
+        if self.rows_done == 0 {
+            return None;
+        }
+
+        let per_row = self.elapsed.as_secs_f32() / self.rows_done as f32;
+        let rows_left = self.total_rows - self.rows_done;
+        Some(Duration::from_secs_f32(per_row * rows_left as f32))
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RenderStatus {
+    Completed,
+    Cancelled,
+}
+
+/// color of a single pixel, shared by every render entry point
+pub fn render_pixel(world: &World, camera: &Camera, x: u32, y: u32) -> Color {
+    render_sample(world, camera, x as f32, y as f32)
+}
+
+/// color of a single sample at sub-pixel position (x, y)
+pub fn render_sample(world: &World, camera: &Camera, x: f32, y: f32) -> Color {
+    let ray = camera.get_ray_subpixel(x, y);
+    world.shade(&ray)
+}
+
+pub fn render(world: &World, camera: &Camera, canvas: &mut Canvas) {
+    render_with_progress(world, camera, canvas, &CancelToken::new(), |_| {});
+}
+
+/// same as `render`, also returning ray counts and timings for the frame
+pub fn render_with_stats(world: &World, camera: &Camera, canvas: &mut Canvas) -> RenderStats {
+    world.take_stats();
+    world.enable_stats(true);
+    render(world, camera, canvas);
+    world.enable_stats(false);
+    world.take_stats()
+}
+
+/// renders row by row, calling `on_progress` after each row.
+/// stops early when `cancel` is set; rows already rendered are kept in `canvas`.
+/// `World::outline` is drawn once every row is done.
+/// to report through a channel, send from the callback: `|p| tx.send(*p).unwrap()`
+pub fn render_with_progress<F>(
+    world: &World,
+    camera: &Camera,
+    canvas: &mut Canvas,
+    cancel: &CancelToken,
+    mut on_progress: F,
+) -> RenderStatus
+where
+    F: FnMut(&RenderProgress),
+{
+    assert_eq!(canvas.get_size(), (camera.hsize, camera.vsize));
+
+    let start = Instant::now();
+    let mut progress = RenderProgress {
+        rows_done: 0,
+        total_rows: camera.vsize,
+        rays: 0,
+        elapsed: Duration::default(),
+    };
+
+    for iy in 0..camera.vsize {
+        if cancel.is_cancelled() {
+            return RenderStatus::Cancelled;
+        }
+
+        for ix in 0..camera.hsize {
+            let color = render_pixel(world, camera, ix, iy);
+            canvas.write_pixel(ix, iy, color);
+        }
+
+        progress.rows_done += 1;
+        progress.rays += camera.hsize as u64;
+        progress.elapsed = start.elapsed();
+        on_progress(&progress);
+    }
+
+    if let Some(outline) = &world.outline {
+        outline.draw(world, camera, canvas);
+    }
+
+    RenderStatus::Completed
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{lib_test::assert_almost_eq_f32, point, vector, view_transform, Angle, PointLight, Sphere};
+
+    use super::*;
+
+    fn test_camera(hsize: u32, vsize: u32) -> Camera {
+        let view_mat = view_transform(point(0.0, 0.0, -5.0), point(0.0, 0.0, 0.0), vector(0.0, 1.0, 0.0));
+        Camera::new(
+            hsize,
+            vsize,
+            Angle::from_degree(60.0),
+            hsize as f32 / vsize as f32,
+            view_mat,
+        )
+    }
+
+    #[test]
+    fn progress_reaches_all_rows() {
+        let world = World::default();
+        let camera = test_camera(11, 7);
+        let mut canvas = Canvas::new(11, 7, None);
+
+        let mut reported = Vec::new();
+        let status = render_with_progress(&world, &camera, &mut canvas, &CancelToken::new(), |p| {
+            reported.push(p.rows_done)
+        });
+
+        assert_eq!(status, RenderStatus::Completed);
+        assert_eq!(reported, (1..=7).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn cancel_keeps_partial_canvas() {
+        let world = World::default();
+        let camera = test_camera(11, 11);
+        let mut canvas = Canvas::new(11, 11, None);
+
+        let cancel = CancelToken::new();
+        let status = render_with_progress(&world, &camera, &mut canvas, &cancel.clone(), |p| {
+            if p.rows_done == 6 {
+                cancel.cancel();
+            }
+        });
+
+        assert_eq!(status, RenderStatus::Cancelled);
+        // both rows hit the sphere, but only the first was rendered before cancel
+        assert!(canvas.pixel_at(5, 5).red > 0.0);
+        assert_eq!(canvas.pixel_at(5, 6).red, 0.0);
+    }
+
+    #[test]
+    fn stats_alongside_image() {
+        let world = World::default();
+        let camera = test_camera(11, 7);
+        let mut canvas = Canvas::new(11, 7, None);
+
+        let stats = render_with_stats(&world, &camera, &mut canvas);
+        assert_eq!(stats.primary_rays, 77);
+        assert!(stats.shadow_rays > 0);
+    }
+
+    #[test]
+    fn highlight_placement_near_frame_edge() {
+        // light sits at the eye, so the highlight is where the sphere faces the camera:
+        // on the line from the eye through the sphere center, which projects to pixel (18, 10)
+        let mut world = World::new();
+        world.add_object(Sphere::new(point(4.0, 0.0, 5.0), 1.0));
+        world.add_light(PointLight::new(point(0.0, 0.0, 0.0), Color::WHITE));
+
+        let view_mat = view_transform(point(0.0, 0.0, 0.0), point(0.0, 0.0, 1.0), vector(0.0, 1.0, 0.0));
+        let camera = Camera::new(21, 21, Angle::from_degree(90.0), 1.0, view_mat);
+        let mut canvas = Canvas::new(21, 21, None);
+        render(&world, &camera, &mut canvas);
+
+        let mut brightest = (0, 0);
+        for y in 0..21 {
+            for x in 0..21 {
+                if canvas.pixel_at(x, y).red > canvas.pixel_at(brightest.0, brightest.1).red {
+                    brightest = (x, y);
+                }
+            }
+        }
+        assert_eq!(brightest, (18, 10));
+    }
+
+    #[test]
+    fn eta_from_progress() {
+        let progress = RenderProgress {
+            rows_done: 25,
+            total_rows: 100,
+            rays: 1000,
+            elapsed: Duration::from_secs(10),
+        };
+        assert_eq!(progress.fraction(), 0.25);
+        assert_eq!(progress.rays_per_sec(), 100.0);
+        assert_almost_eq_f32(progress.eta().unwrap().as_secs_f32(), 30.0);
+    }
+}
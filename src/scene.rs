@@ -0,0 +1,626 @@
+use std::fmt;
+
+use crate::{
+    point, vector, view_transform, Aabb, Angle, AnisotropicMicrofacet, Blackbody, BumpMap, Camera, Checker2dPattern,
+    CheckerPattern, ClearCoat, Color, DirectionalLight, Dispersion, GradientPattern, Material, Matrix, Microfacet,
+    Outline, Pattern, Perlin, PerturbedPattern, Phong, PointLight, RadialGradientPattern, RingPattern, SampledSpectrum,
+    Sphere, SpotLight, StripePattern, Subsurface, ThinFilm, Toon, World, RGB_WAVELENGTHS,
+};
+
+/// largest image a scene may ask for, so a scene from the network cannot exhaust memory (8192 x 8192)
+pub const MAX_IMAGE_PIXELS: u64 = 8192 * 8192;
+
+/// text scene description, one item per line. `#` starts a comment.
+///
+/// ```text
+/// camera <hsize> <vsize> <fov degree> <from x y z> <to x y z> <up x y z>
+/// light <x y z> <r g b> [kelvin]
+/// sun <direction x y z> <r g b> [kelvin]
+/// spot <x y z> <direction x y z> <inner degree> <outer degree> <r g b> [kelvin]
+/// spectral <samples>
+/// indirect <samples>
+/// outline [depth threshold] [crease degree]
+/// sphere <center x y z> <radius> [material]
+/// aabb <min x y z> <max x y z> <outside|inside> [material]
+/// ```
+///
+/// the camera needs at least 2x2 and at most `MAX_IMAGE_PIXELS` pixels, spheres a positive radius.
+///
+/// material is a list of `key=value`:
+/// `color=r,g,b pattern=solid|stripe|gradient|ring|check|checker2d|radial color2=r,g,b pattern_scale= perturb= bump= bump_frequency=`
+/// `ambient= diffuse= diffuse_roughness= specular= shininess= reflective= transparency= refractive_index=`
+/// `model=phong|microfacet|anisotropic|toon metallic= roughness= roughness_u= roughness_v= bands= rim= rim_width=`
+/// `clearcoat= clearcoat_roughness= emission=r,g,b emission_strength=`
+/// `dispersion=bk7|sf11|diamond|water thin_film=<nm> thin_film_index= reflectance=v,v,...`
+/// `subsurface=r,g,b mean_free_path=`
+///
+/// a pattern blends `color` with `color2` (default: half of `color`) and is evaluated in object space.
+/// `perturb` jitters it with noise: a perturbed stripe looks like marble, a perturbed ring like wood.
+/// `bump` roughens the shading normal with perlin noise (`bump_frequency`, default 4).
+/// `dispersion` also sets `refractive_index` to the glass's index for green light.
+/// `thin_film` puts a soap film (`thin_film_index`, default 1.33) of that thickness on the surface.
+///
+/// `subsurface` diffuses light under the surface with that scattering albedo instead of the diffuse term of any model.
+/// `mean_free_path` (default 0.1, or r,g,b) sets how far it travels.
+///
+/// `model=toon` shades in `bands` flat steps with a `rim` light (see `Toon`). `outline` inks silhouettes
+/// and creases over the image (see `Outline`, default 0.1 and 45 degrees).
+///
+/// `indirect` gathers light bounced off other surfaces, so `emission` lights the scene
+/// (see `World::indirect_samples`).
+///
+/// `spectral` renders with that many wavelengths per ray (see `World::spectral_samples`). there a light
+/// with `kelvin` (500 and up) shines like a black body of the rgb intensity's luminance, and `reflectance` is a
+/// measured spectrum spread evenly from 380 to 720 nm that replaces the upsampled color
+pub fn parse_scene(src: &str) -> Result<(World, Camera), SceneError> {
+    let mut world = World::new();
+    let mut camera = None;
+
+    for (idx, line) in src.lines().enumerate() {
+        let line_no = idx + 1;
+        let line = line.split('#').next().unwrap().trim();
+        if line.is_empty() {
+            continue;
+        }
+
+        let mut tokens = Tokens::new(line, line_no);
+        let kind = tokens.next_str()?;
+        match kind {
+            "camera" => {
+                let hsize = tokens.next_u32()?;
+                let vsize = tokens.next_u32()?;
+                let fov = tokens.next_f32()?;
+                let from = tokens.next_point()?;
+                let to = tokens.next_point()?;
+                let up = tokens.next_vector()?;
+                tokens.expect_end()?;
+
+                // rays are spread from the first to the last pixel, so each side needs two
+                if hsize < 2 || vsize < 2 {
+                    return Err(tokens.error(format!("camera must be at least 2x2, found {}x{}", hsize, vsize)));
+                }
+                if u64::from(hsize) * u64::from(vsize) > MAX_IMAGE_PIXELS {
+                    return Err(tokens.error(format!(
+                        "camera {}x{} has more than {} pixels",
+                        hsize, vsize, MAX_IMAGE_PIXELS
+                    )));
+                }
+                let spread = (to - from).cross(up).mag();
+                if !(spread > 0.0 && spread.is_finite()) {
+                    return Err(tokens.error("camera from and to must differ, with up not along the view".to_string()));
+                }
+
+                camera = Some(Camera::new(
+                    hsize,
+                    vsize,
+                    Angle::from_degree(fov),
+                    hsize as f32 / vsize as f32,
+                    view_transform(from, to, up),
+                ));
+            }
+            "light" => {
+                let pos = tokens.next_point()?;
+                let intensity = tokens.next_color()?;
+                let mut light = PointLight::new(pos, intensity);
+                if let Some(kelvin) = tokens.next_kelvin()? {
+                    light.spectrum = Some(Box::new(Blackbody::new(kelvin, intensity.luminance())));
+                }
+                tokens.expect_end()?;
+                world.add_light(light);
+            }
+            "sun" => {
+                let direction = tokens.next_direction()?;
+                let intensity = tokens.next_color()?;
+                let mut sun = DirectionalLight::new(direction, intensity);
+                if let Some(kelvin) = tokens.next_kelvin()? {
+                    sun.spectrum = Some(Box::new(Blackbody::new(kelvin, intensity.luminance())));
+                }
+                tokens.expect_end()?;
+                world.add_light(sun);
+            }
+            "spot" => {
+                let pos = tokens.next_point()?;
+                let direction = tokens.next_direction()?;
+                let inner = Angle::from_degree(tokens.next_f32()?);
+                let outer = Angle::from_degree(tokens.next_f32()?);
+                let intensity = tokens.next_color()?;
+                let mut spot = SpotLight::new(pos, direction, inner, outer, intensity);
+                if let Some(kelvin) = tokens.next_kelvin()? {
+                    spot.light.spectrum = Some(Box::new(Blackbody::new(kelvin, intensity.luminance())));
+                }
+                tokens.expect_end()?;
+                world.add_light(spot);
+            }
+            "spectral" => {
+                world.spectral_samples = tokens.next_u32()?;
+                tokens.expect_end()?;
+            }
+            "indirect" => {
+                world.indirect_samples = tokens.next_u32()?;
+                tokens.expect_end()?;
+            }
+            "outline" => {
+                let mut outline = Outline::default();
+                if let Some(depth_threshold) = tokens.next_optional_f32()? {
+                    outline.depth_threshold = depth_threshold;
+                }
+                if let Some(crease) = tokens.next_optional_f32()? {
+                    outline.crease_angle = Angle::from_degree(crease);
+                }
+                tokens.expect_end()?;
+                world.outline = Some(outline);
+            }
+            "sphere" => {
+                let center = tokens.next_point()?;
+                let r = tokens.next_f32()?;
+                if !(r > 0.0 && r.is_finite()) {
+                    return Err(tokens.error(format!("sphere radius must be positive, found {}", r)));
+                }
+                let mut sphere = Sphere::new(center, r);
+                sphere.mat = tokens.rest_material()?;
+                world.add_object(sphere);
+            }
+            "aabb" => {
+                let min = tokens.next_point()?;
+                let max = tokens.next_point()?;
+                let normal_outside = match tokens.next_str()? {
+                    "outside" => true,
+                    "inside" => false,
+                    other => return Err(tokens.error(format!("expected outside or inside, found {}", other))),
+                };
+                let mut aabb = Aabb::new(min, max, normal_outside);
+                aabb.mat = tokens.rest_material()?;
+                world.add_object(aabb);
+            }
+            other => return Err(tokens.error(format!("unknown item {}", other))),
+        }
+    }
+
+    match camera {
+        Some(camera) => Ok((world, camera)),
+        None => Err(SceneError {
+            line: 0,
+            message: "scene has no camera".to_string(),
+        }),
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct SceneError {
+    pub line: usize,
+    pub message: String,
+}
+
+impl fmt::Display for SceneError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "line {}: {}", self.line, self.message)
+    }
+}
+
+impl std::error::Error for SceneError {}
+
+struct Tokens<'a> {
+    iter: std::str::SplitWhitespace<'a>,
+    line: usize,
+}
+
+impl<'a> Tokens<'a> {
+    fn new(line: &'a str, line_no: usize) -> Self {
+        Self {
+            iter: line.split_whitespace(),
+            line: line_no,
+        }
+    }
+
+    fn error(&self, message: String) -> SceneError {
+        SceneError {
+            line: self.line,
+            message,
+        }
+    }
+
+    fn next_str(&mut self) -> Result<&'a str, SceneError> {
+        let line = self.line;
+        self.iter.next().ok_or_else(|| SceneError {
+            line,
+            message: "unexpected end of line".to_string(),
+        })
+    }
+
+    fn next_f32(&mut self) -> Result<f32, SceneError> {
+        let token = self.next_str()?;
+        parse_f32(token).map_err(|message| self.error(message))
+    }
+
+    fn next_optional_f32(&mut self) -> Result<Option<f32>, SceneError> {
+        match self.iter.next() {
+            Some(token) => parse_f32(token).map(Some).map_err(|message| self.error(message)),
+            None => Ok(None),
+        }
+    }
+
+    /// optional black body temperature, for spectral lights
+    fn next_kelvin(&mut self) -> Result<Option<f32>, SceneError> {
+        let kelvin = self.next_optional_f32()?;
+        match kelvin {
+            Some(kelvin) if !(kelvin >= Blackbody::MIN_KELVIN && kelvin.is_finite()) => Err(self.error(format!(
+                "kelvin must be at least {}, found {}",
+                Blackbody::MIN_KELVIN,
+                kelvin
+            ))),
+            _ => Ok(kelvin),
+        }
+    }
+
+    fn next_u32(&mut self) -> Result<u32, SceneError> {
+        let token = self.next_str()?;
+        token
+            .parse()
+            .map_err(|_| self.error(format!("expected integer, found {}", token)))
+    }
+
+    fn next_point(&mut self) -> Result<crate::Vec4, SceneError> {
+        Ok(point(self.next_f32()?, self.next_f32()?, self.next_f32()?))
+    }
+
+    fn next_vector(&mut self) -> Result<crate::Vec4, SceneError> {
+        Ok(vector(self.next_f32()?, self.next_f32()?, self.next_f32()?))
+    }
+
+    /// a vector that can be normalized
+    fn next_direction(&mut self) -> Result<crate::Vec4, SceneError> {
+        let direction = self.next_vector()?;
+        if !(direction.mag() > 0.0 && direction.mag().is_finite()) {
+            return Err(self.error("direction must be a non-zero vector".to_string()));
+        }
+        Ok(direction)
+    }
+
+    fn next_color(&mut self) -> Result<Color, SceneError> {
+        Ok(Color::new(self.next_f32()?, self.next_f32()?, self.next_f32()?))
+    }
+
+    fn expect_end(&mut self) -> Result<(), SceneError> {
+        match self.iter.next() {
+            Some(token) => Err(self.error(format!("unexpected {}", token))),
+            None => Ok(()),
+        }
+    }
+
+    fn rest_material(&mut self) -> Result<Material, SceneError> {
+        let mut mat = Material::default();
+        let mut pattern_name = "solid";
+        let mut color2 = None;
+        let mut pattern_scale = 1.0;
+        let mut perturb = 0.0;
+        let mut bump = 0.0;
+        let mut bump_frequency = 4.0;
+        let mut model = "phong";
+        let mut metallic = 0.0;
+        let mut roughness = 0.5;
+        let mut clearcoat = 0.0;
+        let mut clearcoat_roughness = ClearCoat::default().roughness;
+        let mut roughness_u = None;
+        let mut roughness_v = None;
+        let mut thin_film = 0.0;
+        let mut thin_film_index = 1.33;
+        let mut subsurface = None;
+        let mut mean_free_path = Color::WHITE * 0.1;
+        let mut toon = Toon::default();
+
+        let rest: Vec<&str> = self.iter.by_ref().collect();
+        for token in rest {
+            let (key, value) = token
+                .split_once('=')
+                .ok_or_else(|| self.error(format!("expected key=value, found {}", token)))?;
+
+            let number = || parse_f32(value).map_err(|message| self.error(message));
+            match key {
+                "color" => mat.color = parse_color(value).map_err(|message| self.error(message))?,
+                "pattern" => pattern_name = value,
+                "color2" => color2 = Some(parse_color(value).map_err(|message| self.error(message))?),
+                "pattern_scale" => {
+                    pattern_scale = number()?;
+                    if pattern_scale == 0.0 || !pattern_scale.is_finite() {
+                        return Err(self.error(format!("pattern_scale must be a non-zero number, found {}", value)));
+                    }
+                }
+                "perturb" => perturb = number()?,
+                "model" => model = value,
+                "metallic" => metallic = number()?,
+                "roughness" => roughness = number()?,
+                "clearcoat" => clearcoat = number()?,
+                "clearcoat_roughness" => clearcoat_roughness = number()?,
+                "roughness_u" => roughness_u = Some(number()?),
+                "roughness_v" => roughness_v = Some(number()?),
+                "bands" => toon.bands = number()? as u32,
+                "rim" => toon.rim = number()?,
+                "rim_width" => toon.rim_width = number()?,
+                "diffuse_roughness" => mat.diffuse_roughness = number()?,
+                "bump" => bump = number()?,
+                "bump_frequency" => bump_frequency = number()?,
+                "ambient" => mat.ambient = number()?,
+                "diffuse" => mat.diffuse = number()?,
+                "specular" => mat.specular = number()?,
+                "shininess" => mat.shininess = number()?,
+                "reflective" => mat.reflective = number()?,
+                "transparency" => mat.transparency = number()?,
+                "refractive_index" => mat.refractive_index = number()?,
+                "emission" => mat.emission = parse_color(value).map_err(|message| self.error(message))?,
+                "emission_strength" => mat.emission_strength = number()?,
+                "dispersion" => {
+                    let dispersion = match value {
+                        "bk7" => Dispersion::BK7,
+                        "sf11" => Dispersion::SF11,
+                        "diamond" => Dispersion::DIAMOND,
+                        "water" => Dispersion::WATER,
+                        _ => return Err(self.error(format!("unknown dispersion {}", value))),
+                    };
+                    mat.refractive_index = dispersion.index_at(RGB_WAVELENGTHS[1]);
+                    mat.dispersion = Some(dispersion);
+                }
+                "thin_film" => thin_film = number()?,
+                "thin_film_index" => thin_film_index = number()?,
+                "subsurface" => subsurface = Some(parse_color(value).map_err(|message| self.error(message))?),
+                "mean_free_path" => {
+                    mean_free_path = if value.contains(',') {
+                        parse_color(value).map_err(|message| self.error(message))?
+                    } else {
+                        Color::WHITE * number()?
+                    };
+                    let channels = [mean_free_path.red, mean_free_path.green, mean_free_path.blue];
+                    if !channels.iter().all(|v| v.is_finite() && *v > 0.0) {
+                        return Err(self.error(format!("mean_free_path must be positive, found {}", value)));
+                    }
+                }
+                "reflectance" => {
+                    let values = value
+                        .split(',')
+                        .map(parse_f32)
+                        .collect::<Result<Vec<_>, _>>()
+                        .map_err(|message| self.error(message))?;
+                    mat.spectrum = Some(Box::new(SampledSpectrum::visible(values)));
+                }
+                _ => return Err(self.error(format!("unknown material key {}", key))),
+            }
+        }
+
+        mat.bsdf = match model {
+            "phong" => Box::new(Phong),
+            "microfacet" => Box::new(Microfacet { metallic, roughness }),
+            "anisotropic" => Box::new(AnisotropicMicrofacet {
+                metallic,
+                roughness_u: roughness_u.unwrap_or(roughness),
+                roughness_v: roughness_v.unwrap_or(roughness),
+            }),
+            "toon" => Box::new(toon),
+            _ => return Err(self.error(format!("unknown model {}", model))),
+        };
+
+        if clearcoat > 0.0 {
+            mat.clearcoat = Some(ClearCoat {
+                weight: clearcoat,
+                roughness: clearcoat_roughness,
+                ..Default::default()
+            });
+        }
+
+        if thin_film > 0.0 {
+            mat.thin_film = Some(ThinFilm {
+                thickness: thin_film,
+                refractive_index: thin_film_index,
+            });
+        }
+
+        if let Some(albedo) = subsurface {
+            let subsurface = Subsurface::from_albedo(albedo, mean_free_path)
+                .ok_or_else(|| self.error("subsurface albedo must be within 0 and 1".to_string()))?;
+            mat.subsurface = Some(subsurface);
+        }
+
+        if bump != 0.0 {
+            mat.normal_mapping = Some(Box::new(BumpMap::from_noise(Perlin::new(0), bump_frequency, bump)));
+        }
+
+        let a = mat.color;
+        let b = color2.unwrap_or(a * 0.5);
+        let mut pattern: Box<dyn Pattern> = match pattern_name {
+            "solid" => return Ok(mat),
+            "stripe" => Box::new(StripePattern::new(a, b)),
+            "gradient" => Box::new(GradientPattern::new(a, b)),
+            "ring" => Box::new(RingPattern::new(a, b)),
+            "check" => Box::new(CheckerPattern::new(a, b)),
+            "checker2d" => Box::new(Checker2dPattern::new(a, b)),
+            "radial" => Box::new(RadialGradientPattern::new(a, b)),
+            _ => return Err(self.error(format!("unknown pattern {}", pattern_name))),
+        };
+        if perturb != 0.0 {
+            pattern = Box::new(PerturbedPattern::new(pattern, perturb));
+        }
+        pattern
+            .set_transform(Matrix::scale(point(pattern_scale, pattern_scale, pattern_scale)))
+            .ok_or_else(|| self.error(format!("pattern_scale {} is too small", pattern_scale)))?;
+        mat.pattern = Some(pattern);
+
+        Ok(mat)
+    }
+}
+
+fn parse_f32(token: &str) -> Result<f32, String> {
+    token.parse().map_err(|_| format!("expected number, found {}", token))
+}
+
+fn parse_color(token: &str) -> Result<Color, String> {
+    let values = token.split(',').map(parse_f32).collect::<Result<Vec<_>, _>>()?;
+    match values[..] {
+        [r, g, b] => Ok(Color::new(r, g, b)),
+        _ => Err(format!("expected r,g,b, found {}", token)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::lib_test::{assert_almost_eq_color, assert_almost_eq_f32};
+
+    use super::*;
+
+    const SCENE: &str = "
+        # two spheres in a room
+        camera 160 90 50  -3 2 5.5  2 0 12  0 1 0
+        light -5 5 5  0.9 0.9 0.9
+        sphere -0.5 1 10  1  color=0.8,0.4,0.2 ambient=0.2 diffuse=0.8 specular=1 shininess=200
+        aabb -4 0 -25  4 100 13  inside  color=0.3,0.3,0.3 pattern=check
+    ";
+
+    #[test]
+    fn parse_example_scene() {
+        let (world, camera) = parse_scene(SCENE).unwrap();
+
+        assert_eq!((camera.hsize, camera.vsize), (160, 90));
+        assert_almost_eq_f32(camera.fov.degree(), 50.0);
+        assert_eq!(world.objects.len(), 2);
+        assert_eq!(world.lights.len(), 1);
+        let light = world.lights[0].illuminate(point(-5.0, 0.0, 5.0), None);
+        assert_almost_eq_color(light.intensity, Color::new(0.9, 0.9, 0.9));
+        assert_almost_eq_f32(light.distance, 5.0);
+    }
+
+    #[test]
+    fn error_reports_line() {
+        let err = parse_scene("camera 10 10 50 0 0 0 0 0 1 0 1 0\nsphere 0 0 0 1 glossy=1")
+            .err()
+            .unwrap();
+        assert_eq!(err.line, 2);
+        assert_eq!(err.message, "unknown material key glossy");
+
+        let err = parse_scene("sphere 0 0").err().unwrap();
+        assert_eq!(err.line, 1);
+    }
+
+    #[test]
+    fn scenes_that_cannot_render_are_rejected() {
+        for src in [
+            "camera 1 1 50 0 0 0 0 0 1 0 1 0",
+            "camera 10 0 50 0 0 0 0 0 1 0 1 0",
+            "camera 70000 70000 50 0 0 0 0 0 1 0 1 0",
+            "camera 10 10 50 0 0 0 0 0 0 0 1 0",
+            "camera 10 10 50 0 0 0 0 1 0 0 1 0",
+            "camera 10 10 50 0 0 0 0 0 1 0 1 0\nsphere 0 0 0 0",
+            "camera 10 10 50 0 0 0 0 0 1 0 1 0\nsphere 0 0 0 -1",
+        ] {
+            assert!(parse_scene(src).is_err(), "{}", src);
+        }
+    }
+
+    #[test]
+    fn parse_pattern() {
+        let src = "camera 10 10 50 0 0 0 0 0 1 0 1 0\nsphere 0 0 0 1 color=1,1,1 pattern=stripe color2=0,0,0 pattern_scale=0.5";
+        let (world, _) = parse_scene(src).unwrap();
+        let hits = world.objects[0].ray_intersect(&crate::Ray::new(point(0.75, 0.0, -5.0), vector(0.0, 0.0, 1.0)));
+        let mat = hits[0].material;
+        assert_almost_eq_color(mat.color(point(0.25, 0.0, 0.0), (0.0, 0.0)), Color::WHITE);
+        assert_almost_eq_color(mat.color(point(0.75, 0.0, 0.0), (0.0, 0.0)), Color::BLACK);
+
+        let err = parse_scene("sphere 0 0 0 1 pattern=plaid").err().unwrap();
+        assert_eq!(err.message, "unknown pattern plaid");
+
+        for scale in ["0", "inf", "0.001"] {
+            let src = format!("sphere 0 0 0 1 pattern=stripe pattern_scale={}", scale);
+            assert!(parse_scene(&src).is_err(), "{}", scale);
+        }
+    }
+
+    #[test]
+    fn parse_sun() {
+        let (world, _) = parse_scene("camera 10 10 50 0 0 0 0 0 1 0 1 0\nsun 1 -1 0 0.5 0.5 0.5").unwrap();
+        let sun = world.lights[0].illuminate(point(0.0, 0.0, 0.0), None);
+        assert_eq!(sun.distance, f32::INFINITY);
+        assert_almost_eq_f32(sun.lightv[0], -(0.5f32.sqrt()));
+        assert_almost_eq_color(sun.intensity, Color::WHITE * 0.5);
+        assert!(parse_scene("camera 10 10 50 0 0 0 0 0 1 0 1 0\nsun 0 0 0 1 1 1").is_err());
+    }
+
+    #[test]
+    fn parse_spot() {
+        let (world, _) = parse_scene("camera 10 10 50 0 0 0 0 0 1 0 1 0\nspot 0 5 0 0 -1 0 20 30 1 1 1").unwrap();
+        let lit = |x| world.lights[0].illuminate(point(x, 0.0, 0.0), None).intensity.red;
+        assert_eq!(lit(0.0), 1.0);
+        assert_eq!(lit(4.0), 0.0);
+        assert_almost_eq_f32(world.lights[0].illuminate(point(0.0, 0.0, 0.0), None).distance, 5.0);
+        assert!(parse_scene("camera 10 10 50 0 0 0 0 0 1 0 1 0\nspot 0 5 0 0 0 0 20 30 1 1 1").is_err());
+    }
+
+    #[test]
+    fn parse_toon() {
+        let src = "camera 10 10 50 0 0 0 0 0 1 0 1 0\noutline 0.2\nsphere 0 0 5 1 model=toon bands=4 rim=0";
+        let (world, _) = parse_scene(src).unwrap();
+        let outline = world.outline.unwrap();
+        assert_eq!(outline.depth_threshold, 0.2);
+        assert_almost_eq_f32(outline.crease_angle.degree(), 45.0);
+        let hits = world.objects[0].ray_intersect(&crate::Ray::new(point(0.0, 0.0, 0.0), vector(0.0, 0.0, 1.0)));
+        assert!(format!("{:?}", hits[0].material.bsdf).starts_with("Toon { bands: 4,"));
+    }
+
+    #[test]
+    fn parse_subsurface() {
+        let camera = "camera 10 10 50 0 0 0 0 0 1 0 1 0\n";
+        let src = format!(
+            "{}sphere 0 0 5 1 subsurface=0.9,0.8,0.7 mean_free_path=0.2,0.1,0.05",
+            camera
+        );
+        assert!(parse_scene(&src).is_ok());
+        for material in [
+            "mean_free_path=0",
+            "mean_free_path=0.1,-1,0.1",
+            "mean_free_path=inf",
+            "subsurface=1.5,1,1",
+        ] {
+            let src = format!("{}sphere 0 0 5 1 subsurface=0.9,0.9,0.9 {}", camera, material);
+            assert!(parse_scene(&src).is_err(), "{}", material);
+        }
+    }
+
+    #[test]
+    fn parse_indirect() {
+        let src = "camera 10 10 50 0 0 0 0 0 1 0 1 0\nindirect 16\nsphere 0 2 0 1 emission=1,1,1";
+        let (world, _) = parse_scene(src).unwrap();
+        assert_eq!(world.indirect_samples, 16);
+        assert!(parse_scene("camera 10 10 50 0 0 0 0 0 1 0 1 0\nindirect").is_err());
+    }
+
+    #[test]
+    fn parse_spectral() {
+        let src = "camera 10 10 50 0 0 0 0 0 1 0 1 0\nspectral 24\nlight 0 5 0 0.8 0.8 0.8 2700\nsphere 0 0 0 1 reflectance=0.1,0.5,0.9";
+        let (world, _) = parse_scene(src).unwrap();
+        assert_eq!(world.spectral_samples, 24);
+        let intensity_at = |wavelength| {
+            world.lights[0]
+                .illuminate(point(0.0, 0.0, 0.0), Some(wavelength))
+                .intensity
+        };
+        assert!(intensity_at(650.0).red > intensity_at(450.0).red);
+        for light in [
+            "light 0 5 0 1 1 1 0",
+            "sun 0 -1 0 1 1 1 -100",
+            "spot 0 5 0 0 -1 0 20 30 1 1 1 inf",
+        ] {
+            assert!(
+                parse_scene(&format!("camera 10 10 50 0 0 0 0 0 1 0 1 0\n{}", light)).is_err(),
+                "{}",
+                light
+            );
+        }
+
+        let hits = world.objects[0].ray_intersect(&crate::Ray::new(point(0.0, 0.0, -5.0), vector(0.0, 0.0, 1.0)));
+        assert_almost_eq_f32(
+            hits[0].material.reflectance_at(hits[0].object_pos, hits[0].uv, 550.0),
+            0.5,
+        );
+    }
+
+    #[test]
+    fn scene_needs_camera() {
+        let err = parse_scene("light 0 0 0 1 1 1").err().unwrap();
+        assert_eq!(err.message, "scene has no camera");
+    }
+}
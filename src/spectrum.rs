@@ -0,0 +1,272 @@
+use std::fmt;
+
+use crate::Color;
+
+/// wavelengths (nm) spectral rendering samples. the cie curves are tiny outside of it
+pub const VISIBLE_MIN: f32 = 380.0;
+pub const VISIBLE_MAX: f32 = 720.0;
+
+/// reflectance or emission as a function of wavelength, for spectral rendering (see `World::spectral_samples`)
+pub trait Spectrum: fmt::Debug {
+    /// value at `wavelength` nm
+    fn value(&self, wavelength: f32) -> f32;
+}
+
+// smits' basis spectra over 10 bins from 380 to 720 nm
+// (an rgb-to-spectrum conversion for reflectances, 1999)
+const SMITS_WHITE: [f32; 10] = [1.0, 1.0, 0.9999, 0.9993, 0.9992, 0.9998, 1.0, 1.0, 1.0, 1.0];
+const SMITS_CYAN: [f32; 10] = [0.9710, 0.9426, 1.0007, 1.0007, 1.0007, 1.0007, 0.1564, 0.0, 0.0, 0.0];
+const SMITS_MAGENTA: [f32; 10] = [1.0, 1.0, 0.9685, 0.2229, 0.0, 0.0458, 0.8369, 1.0, 1.0, 0.9959];
+const SMITS_YELLOW: [f32; 10] = [0.0001, 0.0, 0.1088, 0.6651, 1.0, 1.0, 0.9996, 0.9586, 0.9685, 0.9840];
+const SMITS_RED: [f32; 10] = [0.1012, 0.0515, 0.0, 0.0, 0.0, 0.0, 0.8325, 1.0149, 1.0149, 1.0149];
+const SMITS_GREEN: [f32; 10] = [0.0, 0.0, 0.0273, 0.7937, 1.0, 0.9418, 0.1719, 0.0, 0.0, 0.0025];
+const SMITS_BLUE: [f32; 10] = [1.0, 1.0, 0.8916, 0.3323, 0.0, 0.0, 0.0003, 0.0369, 0.0483, 0.0496];
+
+/// linear interpolation between bin centers, clamped at both ends
+fn lookup(bins: &[f32; 10], wavelength: f32) -> f32 {
+    let width = (VISIBLE_MAX - VISIBLE_MIN) / 10.0;
+    let x = ((wavelength - VISIBLE_MIN) / width - 0.5).clamp(0.0, 9.0);
+    let i = (x as usize).min(8);
+    let f = x - i as f32;
+    bins[i] * (1.0 - f) + bins[i + 1] * f
+}
+
+/// rgb upsampled to a smooth spectrum. grays (and white) are flat,
+/// and the spectrum converts back to about the same rgb
+impl Spectrum for Color {
+    fn value(&self, wavelength: f32) -> f32 {
+        let (r, g, b) = (self.red, self.green, self.blue);
+        let at = |bins| lookup(bins, wavelength);
+        // white up to the smallest channel, then the secondary and the primary on top
+        if r <= g && r <= b {
+            let (secondary, primary) = if g <= b {
+                ((g - r) * at(&SMITS_CYAN), (b - g) * at(&SMITS_BLUE))
+            } else {
+                ((b - r) * at(&SMITS_CYAN), (g - b) * at(&SMITS_GREEN))
+            };
+            r * at(&SMITS_WHITE) + secondary + primary
+        } else if g <= r && g <= b {
+            let (secondary, primary) = if r <= b {
+                ((r - g) * at(&SMITS_MAGENTA), (b - r) * at(&SMITS_BLUE))
+            } else {
+                ((b - g) * at(&SMITS_MAGENTA), (r - b) * at(&SMITS_RED))
+            };
+            g * at(&SMITS_WHITE) + secondary + primary
+        } else {
+            let (secondary, primary) = if r <= g {
+                ((r - b) * at(&SMITS_YELLOW), (g - r) * at(&SMITS_GREEN))
+            } else {
+                ((g - b) * at(&SMITS_YELLOW), (r - g) * at(&SMITS_RED))
+            };
+            b * at(&SMITS_WHITE) + secondary + primary
+        }
+    }
+}
+
+/// measured data: `values[i]` at `start + i * step` nm, linear in between, clamped outside
+#[derive(Debug, Clone)]
+pub struct SampledSpectrum {
+    pub start: f32,
+    pub step: f32,
+    pub values: Vec<f32>,
+}
+
+impl SampledSpectrum {
+    pub fn new(start: f32, step: f32, values: Vec<f32>) -> Self {
+        assert!(!values.is_empty());
+        Self { start, step, values }
+    }
+
+    /// `values` spread evenly over the visible range
+    pub fn visible(values: Vec<f32>) -> Self {
+        let step = (VISIBLE_MAX - VISIBLE_MIN) / (values.len().max(2) - 1) as f32;
+        Self::new(VISIBLE_MIN, step, values)
+    }
+}
+
+impl Spectrum for SampledSpectrum {
+    fn value(&self, wavelength: f32) -> f32 {
+        let last = self.values.len() - 1;
+        let x = ((wavelength - self.start) / self.step).clamp(0.0, last as f32);
+        let i = (x as usize).min(last.saturating_sub(1));
+        let f = x - i as f32;
+        match self.values.get(i + 1) {
+            Some(next) => self.values[i] * (1.0 - f) + next * f,
+            None => self.values[i],
+        }
+    }
+}
+
+/// planck's law: the light of an incandescent bulb (2700 K), the sun (5800 K), ...
+#[derive(Debug, Clone, Copy)]
+pub struct Blackbody {
+    pub kelvin: f32,
+    /// luminance of the light, as with `Color::luminance` of an rgb intensity
+    pub strength: f32,
+    /// planck's law divided by this has a luminance of 1
+    norm: f32,
+}
+
+impl Blackbody {
+    /// coldest body `new` accepts. colder ones hardly glow in visible light, and planck's law
+    /// underflows there in f32
+    pub const MIN_KELVIN: f32 = 500.0;
+
+    /// `kelvin` must be at least `MIN_KELVIN`, or every value is NaN
+    pub fn new(kelvin: f32, strength: f32) -> Self {
+        debug_assert!(kelvin >= Self::MIN_KELVIN, "black body of {} K", kelvin);
+        let mut emitted = 0.0;
+        let mut sensitivity = 0.0;
+        for wavelength in stratified_wavelengths(340) {
+            let y = cie_xyz(wavelength)[1];
+            emitted += planck(wavelength, kelvin) * y;
+            sensitivity += y;
+        }
+
+        Self {
+            kelvin,
+            strength,
+            norm: emitted / sensitivity,
+        }
+    }
+}
+
+impl Spectrum for Blackbody {
+    fn value(&self, wavelength: f32) -> f32 {
+        planck(wavelength, self.kelvin) / self.norm * self.strength
+    }
+}
+
+/// unscaled spectral radiance of a black body
+fn planck(wavelength: f32, kelvin: f32) -> f32 {
+    // second radiation constant hc/k in nm K
+    const C2: f32 = 1.438_777e7;
+    let um = wavelength / 1000.0;
+    1.0 / (um.powi(5) * ((C2 / (wavelength * kelvin)).exp() - 1.0))
+}
+
+/// `n` wavelengths at the centers of equal slices of the visible range
+pub fn stratified_wavelengths(n: u32) -> impl Iterator<Item = f32> {
+    let width = (VISIBLE_MAX - VISIBLE_MIN) / n as f32;
+    (0..n).map(move |i| VISIBLE_MIN + (i as f32 + 0.5) * width)
+}
+
+/// cie 1931 color matching functions (x̄, ȳ, z̄), by the multi-lobe fit of
+/// wyman, sloan and shirley (simple analytic approximations to the cie xyz color matching functions, 2013)
+pub fn cie_xyz(wavelength: f32) -> [f32; 3] {
+    let lobe = |mean: f32, sigma_below: f32, sigma_above: f32| {
+        let sigma = if wavelength < mean { sigma_below } else { sigma_above };
+        let t = (wavelength - mean) / sigma;
+        (-0.5 * t * t).exp()
+    };
+
+    [
+        1.056 * lobe(599.8, 37.9, 31.0) + 0.362 * lobe(442.0, 16.0, 26.7) - 0.065 * lobe(501.1, 20.4, 26.2),
+        0.821 * lobe(568.8, 46.9, 40.5) + 0.286 * lobe(530.9, 16.3, 31.1),
+        1.217 * lobe(437.0, 11.8, 36.0) + 0.681 * lobe(459.0, 26.0, 13.8),
+    ]
+}
+
+/// cie xyz to linear srgb
+pub fn xyz_to_rgb([x, y, z]: [f32; 3]) -> Color {
+    Color::new(
+        3.240_454 * x - 1.537_138_5 * y - 0.498_531_4 * z,
+        -0.969_266 * x + 1.876_010_8 * y + 0.041_556 * z,
+        0.055_643_4 * x - 0.204_025_9 * y + 1.057_225_2 * z,
+    )
+}
+
+/// color of the light whose spectrum is `radiance`, sampled at `samples` wavelengths.
+/// white balanced so a flat spectrum of 1 gives `Color::WHITE`, the way an rgb render sees white light
+pub fn spectrum_to_color<F: FnMut(f32) -> f32>(samples: u32, mut radiance: F) -> Color {
+    let mut xyz = [0.0; 3];
+    let mut white = [0.0; 3];
+    for wavelength in stratified_wavelengths(samples) {
+        let cmf = cie_xyz(wavelength);
+        let value = radiance(wavelength);
+        for i in 0..3 {
+            xyz[i] += cmf[i] * value;
+            white[i] += cmf[i];
+        }
+    }
+
+    let color = xyz_to_rgb(xyz);
+    let white = xyz_to_rgb(white);
+    Color::new(
+        color.red / white.red,
+        color.green / white.green,
+        color.blue / white.blue,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::lib_test::{assert_almost_eq_color, assert_almost_eq_f32};
+
+    use super::*;
+
+    fn round_trip(color: Color) -> Color {
+        spectrum_to_color(64, |wavelength| color.value(wavelength))
+    }
+
+    #[test]
+    fn flat_spectrum_is_white() {
+        assert_almost_eq_color(spectrum_to_color(16, |_| 1.0), Color::WHITE);
+        assert_almost_eq_color(spectrum_to_color(16, |_| 0.25), Color::WHITE * 0.25);
+        assert_almost_eq_f32((Color::WHITE * 0.5).value(450.0), 0.5);
+    }
+
+    #[test]
+    fn rgb_upsampling_round_trips() {
+        for color in [
+            Color::new(1.0, 0.0, 0.0),
+            Color::new(0.0, 1.0, 0.0),
+            Color::new(0.0, 0.0, 1.0),
+            Color::new(0.8, 0.4, 0.2),
+            Color::new(0.2, 0.6, 0.8),
+        ] {
+            let back = round_trip(color);
+            for (a, b) in [
+                (back.red, color.red),
+                (back.green, color.green),
+                (back.blue, color.blue),
+            ] {
+                assert!((a - b).abs() < 0.04, "{:?} -> {:?}", color, back);
+            }
+        }
+    }
+
+    #[test]
+    fn color_matching_peaks() {
+        // ȳ peaks at 555 nm, z̄ in the blue, x̄ has its big lobe in the red
+        assert!(cie_xyz(555.0)[1] > 0.99 && cie_xyz(555.0)[1] < 1.01);
+        assert!(cie_xyz(445.0)[2] > 1.7);
+        assert!(cie_xyz(600.0)[0] > 1.0);
+    }
+
+    #[test]
+    fn blackbody_tint() {
+        let candle = spectrum_to_color(32, |wavelength| Blackbody::new(1900.0, 1.0).value(wavelength));
+        let sky = spectrum_to_color(32, |wavelength| Blackbody::new(12000.0, 1.0).value(wavelength));
+        assert!(candle.red > candle.green && candle.green > candle.blue);
+        assert!(sky.blue > sky.red);
+        // white balancing shifts the luminance a little
+        assert!((candle.luminance() - 1.0).abs() < 0.1, "{:?}", candle);
+    }
+
+    #[test]
+    fn coldest_blackbody_is_finite() {
+        let ember = Blackbody::new(Blackbody::MIN_KELVIN, 1.0);
+        assert!(stratified_wavelengths(16).all(|wavelength| ember.value(wavelength).is_finite()));
+    }
+
+    #[test]
+    fn sampled_spectrum_interpolates() {
+        let spectrum = SampledSpectrum::new(400.0, 100.0, vec![0.0, 1.0, 0.5]);
+        assert_eq!(spectrum.value(450.0), 0.5);
+        assert_eq!(spectrum.value(550.0), 0.75);
+        assert_eq!(spectrum.value(300.0), 0.0);
+        assert_eq!(spectrum.value(700.0), 0.5);
+        assert_eq!(SampledSpectrum::visible(vec![0.3]).value(500.0), 0.3);
+    }
+}
@@ -1,4 +1,4 @@
-use crate::{point, Intersection, Material, Object, Vec4};
+use crate::{point, spherical_map, vector, Intersection, Material, Object, Vec4};
 
 pub struct Sphere {
     pub center: Vec4,
@@ -19,19 +19,43 @@ impl Sphere {
         let dir = p - self.center;
         dir.normalize()
     }
+
+    /// direction of increasing u (see `spherical_map`). u is undefined at the poles, +x is used there
+    pub fn tangent_at(&self, p: Vec4) -> Vec4 {
+        let d = p - self.center;
+        let t = vector(-d[2], 0.0, d[0]);
+        if t.mag() < 1e-6 {
+            vector(1.0, 0.0, 0.0)
+        } else {
+            t.normalize()
+        }
+    }
+
+    /// object space is the unit sphere at the origin
+    pub fn to_object_space(&self, p: Vec4) -> Vec4 {
+        point(0.0, 0.0, 0.0) + (p - self.center) / self.r
+    }
 }
 
 impl Object for Sphere {
-    fn ray_intersect(&self, ray: &crate::Ray) -> Vec<crate::Intersection> {
+    fn ray_intersect(&self, ray: &crate::Ray) -> Vec<crate::Intersection<'_>> {
         let ts = ray.intersect_sphere(self);
         ts.iter()
             .map(|t| {
                 let pos = ray.position(*t);
                 let normalv = self.normal_at(pos);
-                Intersection::new(*t, pos, normalv, &self.mat)
+                let mut i = Intersection::new(*t, pos, normalv, &self.mat);
+                i.object_pos = self.to_object_space(pos);
+                i.uv = spherical_map(i.object_pos);
+                i.tangent = self.tangent_at(pos);
+                i
             })
             .collect()
     }
+
+    fn name(&self) -> &'static str {
+        "sphere"
+    }
 }
 
 impl Default for Sphere {
@@ -46,7 +70,10 @@ impl Default for Sphere {
 
 #[cfg(test)]
 mod tests {
-    use crate::{lib_test::assert_almost_eq_tuple, point, vector};
+    use crate::{
+        lib_test::{assert_almost_eq_f32, assert_almost_eq_tuple},
+        point, vector,
+    };
 
     use super::*;
 
@@ -57,4 +84,15 @@ mod tests {
         let normal = sphere.normal_at(point(sqrt_3_over_3, sqrt_3_over_3, sqrt_3_over_3));
         assert_almost_eq_tuple(normal, vector(sqrt_3_over_3, sqrt_3_over_3, sqrt_3_over_3));
     }
+
+    #[test]
+    fn intersection_in_object_space() {
+        let sphere = Sphere::new(point(5.0, 0.0, 0.0), 2.0);
+        let ray = crate::Ray::new(point(0.0, 0.0, 0.0), vector(1.0, 0.0, 0.0));
+        let xs = sphere.ray_intersect(&ray);
+        assert_almost_eq_tuple(xs[0].object_pos, point(-1.0, 0.0, 0.0));
+        assert_almost_eq_tuple(xs[1].object_pos, point(1.0, 0.0, 0.0));
+        assert_almost_eq_f32(xs[0].uv.0, 0.25);
+        assert_almost_eq_f32(xs[0].uv.1, 0.5);
+    }
 }
@@ -0,0 +1,94 @@
+use std::cell::{Cell, RefCell};
+use std::collections::BTreeMap;
+use std::fmt;
+use std::mem::size_of;
+use std::time::{Duration, Instant};
+
+use crate::Intersection;
+
+/// counters gathered while rendering. see `World::enable_stats`
+#[derive(Debug, Clone, Default)]
+pub struct RenderStats {
+    pub primary_rays: u64,
+    pub shadow_rays: u64,
+    /// reflected / refracted rays
+    pub secondary_rays: u64,
+    /// ray-object tests, keyed by `Object::name`
+    pub intersection_tests: BTreeMap<&'static str, u64>,
+    /// time spent finding intersections
+    pub traversal_time: Duration,
+    /// time spent in shade excluding traversal
+    pub shading_time: Duration,
+    /// longest intersection list built for a single ray
+    pub peak_intersections: usize,
+}
+
+impl RenderStats {
+    pub fn total_rays(&self) -> u64 {
+        self.primary_rays + self.shadow_rays + self.secondary_rays
+    }
+
+    pub fn peak_intersection_bytes(&self) -> usize {
+        self.peak_intersections * size_of::<Intersection>()
+    }
+}
+
+impl fmt::Display for RenderStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "primary rays:      {}", self.primary_rays)?;
+        writeln!(f, "shadow rays:       {}", self.shadow_rays)?;
+        writeln!(f, "secondary rays:    {}", self.secondary_rays)?;
+        for (name, count) in &self.intersection_tests {
+            writeln!(f, "{} tests: {}", name, count)?;
+        }
+        writeln!(f, "traversal time:    {:?}", self.traversal_time)?;
+        writeln!(f, "shading time:      {:?}", self.shading_time)?;
+        write!(
+            f,
+            "peak intersections: {} ({} bytes)",
+            self.peak_intersections,
+            self.peak_intersection_bytes()
+        )
+    }
+}
+
+/// world side of the stats. does nothing (and reads no clock) while disabled
+#[derive(Default)]
+pub(crate) struct StatsCollector {
+    enabled: Cell<bool>,
+    stats: RefCell<RenderStats>,
+    shade_time: Cell<Duration>,
+}
+
+impl StatsCollector {
+    pub(crate) fn set_enabled(&self, enabled: bool) {
+        self.enabled.set(enabled);
+    }
+
+    pub(crate) fn record<F: FnOnce(&mut RenderStats)>(&self, f: F) {
+        if self.enabled.get() {
+            f(&mut self.stats.borrow_mut());
+        }
+    }
+
+    pub(crate) fn start_timer(&self) -> Option<Instant> {
+        if self.enabled.get() {
+            Some(Instant::now())
+        } else {
+            None
+        }
+    }
+
+    pub(crate) fn add_shade_time(&self, start: Option<Instant>) {
+        if let Some(start) = start {
+            self.shade_time.set(self.shade_time.get() + start.elapsed());
+        }
+    }
+
+    pub(crate) fn take(&self) -> RenderStats {
+        let mut stats = self.stats.take();
+        let shade_time = self.shade_time.take();
+        stats.shading_time = shade_time.saturating_sub(stats.traversal_time);
+        stats
+    }
+}
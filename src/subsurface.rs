@@ -0,0 +1,234 @@
+use std::f32::consts::PI;
+use std::num::NonZeroU32;
+
+use crate::{Color, Spectrum};
+
+/// light entering the surface scatters around inside and leaves at other points nearby
+/// (skin, wax, marble, milk). evaluated with the dipole diffusion approximation of
+/// jensen et al. (a practical model for subsurface light transport, 2001).
+/// coefficients are per unit of scene length
+#[derive(Debug, Clone, Copy)]
+pub struct Subsurface {
+    /// reduced scattering coefficient σs'
+    pub scattering: Color,
+    /// absorption coefficient σa
+    pub absorption: Color,
+    /// 1.3 for skin, 1.5 for marble
+    pub refractive_index: f32,
+    /// surface points around a hit the incoming light is gathered from
+    pub samples: NonZeroU32,
+}
+
+impl Subsurface {
+    /// None unless both coefficients are finite and not negative, and their sum is positive in every channel
+    pub fn new(scattering: Color, absorption: Color) -> Option<Self> {
+        let channels = [
+            (scattering.red, absorption.red),
+            (scattering.green, absorption.green),
+            (scattering.blue, absorption.blue),
+        ];
+        let valid = channels.iter().all(|&(scattering, absorption)| {
+            scattering.is_finite()
+                && absorption.is_finite()
+                && scattering >= 0.0
+                && absorption >= 0.0
+                && scattering + absorption > 0.0
+        });
+        if !valid {
+            return None;
+        }
+
+        Some(Self {
+            scattering,
+            absorption,
+            refractive_index: 1.3,
+            samples: NonZeroU32::new(32).unwrap(),
+        })
+    }
+
+    /// `albedo`: share of the light surviving each scattering event (the scattering color; 0.99 and
+    /// up for milk or marble), in [0, 1]. `mean_free_path`: how far light gets between events, per
+    /// channel, positive. None when either is out of range
+    pub fn from_albedo(albedo: Color, mean_free_path: Color) -> Option<Self> {
+        let extinction = Color::new(
+            1.0 / mean_free_path.red,
+            1.0 / mean_free_path.green,
+            1.0 / mean_free_path.blue,
+        );
+        Self::new(albedo * extinction, (Color::WHITE - albedo) * extinction)
+    }
+
+    /// the same medium at a single wavelength, for spectral rendering
+    pub fn at_wavelength(&self, wavelength: f32) -> Self {
+        Self {
+            scattering: Color::WHITE * self.scattering.value(wavelength),
+            absorption: Color::WHITE * self.absorption.value(wavelength),
+            ..*self
+        }
+    }
+
+    /// diffuse reflectance Rd(r): light leaving at distance `r` from where a unit of light entered, per area
+    pub fn profile(&self, r: f32) -> Color {
+        self.per_channel(|channel| channel.profile(r))
+    }
+
+    /// all light that comes back out, the integral of `profile` over the surface
+    pub fn diffuse_reflectance(&self) -> Color {
+        self.per_channel(|channel| channel.total())
+    }
+
+    /// distance and angle around the hit of gathering point `i`, with the probability density of
+    /// picking it per unit area. radii follow the falloff of each channel in turn
+    pub fn sample(&self, i: u32) -> (f32, f32, f32) {
+        let channels = self.channels();
+        let u = (i as f32 + 0.5) / self.samples.get() as f32;
+        let r = -(1.0 - u).ln() / channels[i as usize % 3].sigma_tr();
+        // golden angle, so consecutive radii do not line up
+        let angle = i as f32 * 2.399_963;
+
+        let pdf_r: f32 = channels
+            .iter()
+            .map(|c| c.sigma_tr() * (-c.sigma_tr() * r).exp())
+            .sum::<f32>()
+            / 3.0;
+        (r, angle, pdf_r / (2.0 * PI * r))
+    }
+
+    fn channels(&self) -> [Dipole; 3] {
+        let dipole = |scattering, absorption| Dipole {
+            scattering,
+            absorption,
+            eta: self.refractive_index,
+        };
+        [
+            dipole(self.scattering.red, self.absorption.red),
+            dipole(self.scattering.green, self.absorption.green),
+            dipole(self.scattering.blue, self.absorption.blue),
+        ]
+    }
+
+    fn per_channel<F: Fn(&Dipole) -> f32>(&self, f: F) -> Color {
+        let [r, g, b] = self.channels().map(|channel| f(&channel));
+        Color::new(r, g, b)
+    }
+}
+
+/// one color channel of the dipole model
+struct Dipole {
+    scattering: f32,
+    absorption: f32,
+    eta: f32,
+}
+
+impl Dipole {
+    fn extinction(&self) -> f32 {
+        self.scattering + self.absorption
+    }
+
+    fn albedo(&self) -> f32 {
+        self.scattering / self.extinction()
+    }
+
+    /// effective transport coefficient: how fast diffused light dies off
+    fn sigma_tr(&self) -> f32 {
+        // a little absorption keeps the falloff (and the sampling) finite
+        let absorption = self.absorption.max(self.extinction() * 1e-4);
+        (3.0 * absorption * self.extinction()).sqrt()
+    }
+
+    /// makes up for light reflected back in at the boundary
+    fn boundary(&self) -> f32 {
+        let eta = self.eta;
+        let fdr = -1.440 / (eta * eta) + 0.710 / eta + 0.668 + 0.0636 * eta;
+        (1.0 + fdr) / (1.0 - fdr)
+    }
+
+    fn profile(&self, r: f32) -> f32 {
+        let sigma_tr = self.sigma_tr();
+        // real source below the surface, mirrored virtual source above it
+        let z_real = 1.0 / self.extinction();
+        let z_virtual = z_real * (1.0 + 4.0 / 3.0 * self.boundary());
+        let source = |z: f32| {
+            let d = (r * r + z * z).sqrt();
+            z * (sigma_tr * d + 1.0) * (-sigma_tr * d).exp() / (d * d * d)
+        };
+        self.albedo() / (4.0 * PI) * (source(z_real) + source(z_virtual))
+    }
+
+    fn total(&self) -> f32 {
+        let s = (3.0 * (1.0 - self.albedo())).sqrt();
+        self.albedo() / 2.0 * (1.0 + (-4.0 / 3.0 * self.boundary() * s).exp()) * (-s).exp()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn marble() -> Subsurface {
+        // jensen et al.'s measured marble, per mm
+        Subsurface {
+            refractive_index: 1.5,
+            ..Subsurface::new(Color::new(2.19, 2.62, 3.00), Color::new(0.0021, 0.0041, 0.0071)).unwrap()
+        }
+    }
+
+    #[test]
+    fn profile_integrates_to_diffuse_reflectance() {
+        let sss = marble();
+        let total = sss.diffuse_reflectance();
+        let (n, max_r) = (200_000, 200.0);
+        let dr = max_r / n as f32;
+        let mut integral = Color::BLACK;
+        for i in 0..n {
+            let r = (i as f32 + 0.5) * dr;
+            integral += sss.profile(r) * (2.0 * PI * r * dr);
+        }
+        for (a, b) in [(integral.red, total.red), (integral.blue, total.blue)] {
+            assert!((a - b).abs() < 0.01, "{:?} vs {:?}", integral, total);
+        }
+        // marble is bright and a little warm
+        assert!(total.red > 0.8 && total.red > total.blue);
+    }
+
+    #[test]
+    fn longer_mean_free_path_spreads_light_further() {
+        let albedo = Color::WHITE * 0.95;
+        let short = Subsurface::from_albedo(albedo, Color::WHITE * 0.1).unwrap();
+        let long = Subsurface::from_albedo(albedo, Color::WHITE * 1.0).unwrap();
+        assert!((short.diffuse_reflectance().red - long.diffuse_reflectance().red).abs() < 1e-4);
+        // same total, but reaching further from the entry point
+        assert!(long.profile(2.0).red > 10.0 * short.profile(2.0).red);
+        assert!(long.profile(0.01).red < short.profile(0.01).red);
+    }
+
+    #[test]
+    fn degenerate_media_are_rejected() {
+        assert!(Subsurface::from_albedo(Color::WHITE * 0.9, Color::new(0.1, 0.0, 0.1)).is_none());
+        assert!(Subsurface::from_albedo(Color::WHITE * 1.5, Color::WHITE * 0.1).is_none());
+        assert!(Subsurface::new(Color::BLACK, Color::BLACK).is_none());
+    }
+
+    #[test]
+    fn samples_cover_the_profile() {
+        // sum of profile / pdf estimates the total reflectance
+        let sss = Subsurface {
+            samples: NonZeroU32::new(600).unwrap(),
+            ..Subsurface::from_albedo(Color::new(0.99, 0.9, 0.7), Color::new(0.5, 0.3, 0.2)).unwrap()
+        };
+        let mut estimate = Color::BLACK;
+        for i in 0..sss.samples.get() {
+            let (r, _, pdf) = sss.sample(i);
+            estimate += sss.profile(r) * (1.0 / pdf);
+        }
+        let estimate = estimate * (1.0 / sss.samples.get() as f32);
+        let total = sss.diffuse_reflectance();
+        for (a, b) in [
+            (estimate.red, total.red),
+            (estimate.green, total.green),
+            (estimate.blue, total.blue),
+        ] {
+            assert!((a - b).abs() < 0.05 * b, "{:?} vs {:?}", estimate, total);
+        }
+    }
+}
@@ -0,0 +1,196 @@
+use std::path::Path;
+
+use image::error::{ParameterError, ParameterErrorKind};
+
+use crate::{inv_transform_accessors, point, Color, Matrix, Matrix4, Pattern, Uv, UvMapping, Vec4};
+
+/// what happens to uv outside [0, 1]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WrapMode {
+    Repeat,
+    Clamp,
+}
+
+/// image mapped onto a surface. sampled with bilinear filtering. with `UvMapping::Surface` the
+/// pattern transform moves the image in the uv plane (x = u, y = v), like the other mappings in space
+#[derive(Debug)]
+pub struct ImageTexture {
+    width: u32,
+    height: u32,
+    texels: Vec<Color>,
+    pub wrap: WrapMode,
+    pub mapping: UvMapping,
+    inv_transform: Matrix4,
+}
+
+impl ImageTexture {
+    /// gamma: the image is decoded with pow(color, gamma), the inverse of `Canvas`. 2.2 if None.
+    /// None for an empty image, which has no texel to sample
+    pub fn new(image: &image::RgbImage, gamma: Option<f32>) -> Option<Self> {
+        if image.width() == 0 || image.height() == 0 {
+            return None;
+        }
+
+        let gamma = gamma.unwrap_or(2.2);
+        let texels = image
+            .pixels()
+            .map(|p| {
+                let channel = |c: u8| (c as f32 / 255.0).powf(gamma);
+                Color::new(channel(p[0]), channel(p[1]), channel(p[2]))
+            })
+            .collect();
+
+        Some(Self {
+            width: image.width(),
+            height: image.height(),
+            texels,
+            wrap: WrapMode::Repeat,
+            mapping: UvMapping::Surface,
+            inv_transform: Matrix::identity(),
+        })
+    }
+
+    pub fn open<P: AsRef<Path>>(path: P, gamma: Option<f32>) -> image::ImageResult<Self> {
+        Self::new(&image::open(path)?.to_rgb8(), gamma).ok_or_else(empty_image_error)
+    }
+
+    /// bilinear lookup. v = 0 is the bottom row of the image
+    pub fn sample(&self, (u, v): Uv) -> Color {
+        // wrap or clamp into [0, 1] first, so far away or non-finite uv cannot overflow the texel indices
+        let fit = |t: f32| match self.wrap {
+            _ if !t.is_finite() => 0.0,
+            WrapMode::Repeat => t - t.floor(),
+            WrapMode::Clamp => t.clamp(0.0, 1.0),
+        };
+        let (u, v) = (fit(u), fit(v));
+
+        let x = u * self.width as f32 - 0.5;
+        let y = (1.0 - v) * self.height as f32 - 0.5;
+        let (x0, y0) = (x.floor(), y.floor());
+        let (fx, fy) = (x - x0, y - y0);
+        let (x0, y0) = (x0 as i64, y0 as i64);
+
+        let top = self.texel(x0, y0) * (1.0 - fx) + self.texel(x0 + 1, y0) * fx;
+        let bottom = self.texel(x0, y0 + 1) * (1.0 - fx) + self.texel(x0 + 1, y0 + 1) * fx;
+        top * (1.0 - fy) + bottom * fy
+    }
+
+    fn texel(&self, x: i64, y: i64) -> Color {
+        let (w, h) = (self.width as i64, self.height as i64);
+        let (x, y) = match self.wrap {
+            WrapMode::Repeat => (x.rem_euclid(w), y.rem_euclid(h)),
+            WrapMode::Clamp => (x.clamp(0, w - 1), y.clamp(0, h - 1)),
+        };
+        self.texels[(x + y * w) as usize]
+    }
+}
+
+pub(crate) fn empty_image_error() -> image::ImageError {
+    image::ImageError::Parameter(ParameterError::from_kind(ParameterErrorKind::Generic(
+        "image is empty".to_string(),
+    )))
+}
+
+impl Pattern for ImageTexture {
+    inv_transform_accessors!();
+
+    fn pattern_at(&self, pos: Vec4) -> Color {
+        self.sample(self.mapping.map(pos))
+    }
+
+    fn color_at_uv(&self, object_pos: Vec4, uv: Uv) -> Color {
+        match self.mapping {
+            UvMapping::Surface => {
+                let pos = self.inv_transform * point(uv.0, uv.1, 0.0);
+                self.sample((pos[0], pos[1]))
+            }
+            _ => self.color_at(object_pos),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::lib_test::assert_almost_eq_color;
+
+    use super::*;
+
+    /// 2x2: red green / blue white
+    fn texture() -> ImageTexture {
+        let image = image::RgbImage::from_raw(2, 2, vec![255, 0, 0, 0, 255, 0, 0, 0, 255, 255, 255, 255]).unwrap();
+        ImageTexture::new(&image, Some(1.0)).unwrap()
+    }
+
+    #[test]
+    fn texel_centers() {
+        let texture = texture();
+        assert_almost_eq_color(texture.sample((0.25, 0.75)), Color::new(1.0, 0.0, 0.0));
+        assert_almost_eq_color(texture.sample((0.75, 0.75)), Color::new(0.0, 1.0, 0.0));
+        assert_almost_eq_color(texture.sample((0.25, 0.25)), Color::new(0.0, 0.0, 1.0));
+        assert_almost_eq_color(texture.sample((0.75, 0.25)), Color::WHITE);
+    }
+
+    #[test]
+    fn bilinear_between_texels() {
+        let texture = texture();
+        assert_almost_eq_color(texture.sample((0.5, 0.75)), Color::new(0.5, 0.5, 0.0));
+        assert_almost_eq_color(texture.sample((0.5, 0.5)), Color::new(0.5, 0.5, 0.5));
+    }
+
+    #[test]
+    fn wrap_modes() {
+        let mut texture = texture();
+        // left edge blends with the right column when repeating
+        assert_almost_eq_color(texture.sample((0.0, 0.75)), Color::new(0.5, 0.5, 0.0));
+        assert_almost_eq_color(texture.sample((1.25, 0.75)), Color::new(1.0, 0.0, 0.0));
+
+        texture.wrap = WrapMode::Clamp;
+        assert_almost_eq_color(texture.sample((0.0, 0.75)), Color::new(1.0, 0.0, 0.0));
+        assert_almost_eq_color(texture.sample((1.25, 0.75)), Color::new(0.0, 1.0, 0.0));
+    }
+
+    #[test]
+    fn empty_image_is_rejected() {
+        assert!(ImageTexture::new(&image::RgbImage::new(0, 0), None).is_none());
+        assert!(ImageTexture::new(&image::RgbImage::new(4, 0), None).is_none());
+    }
+
+    #[test]
+    fn gamma_decodes_texels() {
+        let image = image::RgbImage::from_raw(1, 1, vec![128, 128, 128]).unwrap();
+        let texture = ImageTexture::new(&image, None).unwrap();
+        let expected = (128.0_f32 / 255.0).powf(2.2);
+        assert_almost_eq_color(texture.sample((0.5, 0.5)), Color::new(expected, expected, expected));
+    }
+
+    #[test]
+    fn surface_or_own_mapping() {
+        let mut texture = texture();
+        let pos = point(0.75, 0.0, 0.25);
+        assert_almost_eq_color(texture.color_at_uv(pos, (0.25, 0.75)), Color::new(1.0, 0.0, 0.0));
+
+        texture.mapping = UvMapping::Planar;
+        assert_almost_eq_color(texture.color_at_uv(pos, (0.25, 0.75)), Color::WHITE);
+    }
+
+    #[test]
+    fn transform_moves_surface_uv() {
+        let mut texture = texture();
+        // shifted half the image right: the left column now shows the right one
+        texture.set_transform(Matrix::translate(point(0.5, 0.0, 0.0))).unwrap();
+        let pos = point(0.0, 0.0, 0.0);
+        assert_almost_eq_color(texture.color_at_uv(pos, (0.25, 0.75)), Color::new(0.0, 1.0, 0.0));
+    }
+
+    #[test]
+    fn wild_uv_does_not_overflow() {
+        let mut texture = texture();
+        for wrap in [WrapMode::Repeat, WrapMode::Clamp] {
+            texture.wrap = wrap;
+            for uv in [(f32::NAN, 0.5), (f32::INFINITY, f32::NEG_INFINITY), (1e30, -1e30)] {
+                let color = texture.sample(uv);
+                assert!(color.red.is_finite() && color.green.is_finite(), "{:?}", uv);
+            }
+        }
+    }
+}
@@ -0,0 +1,262 @@
+use std::f32::consts::PI;
+use std::ops::Range;
+
+use crate::{get_frontmost_intersection, Angle, Bsdf, Camera, Canvas, Color, Material, ShadingFrame, Vec4, World};
+
+/// cel shading: diffuse light in a few flat bands, a hard edged highlight and a rim of light
+/// around the silhouette. uses `Material::diffuse`, `specular` and `shininess` like `Phong`.
+/// the rim does not depend on the lights: it is added once per point (see `Bsdf::unlit`)
+#[derive(Debug, Clone, Copy)]
+pub struct Toon {
+    /// number of diffuse bands between unlit and fully lit
+    pub bands: u32,
+    /// brightness of the rim light, 0 for none
+    pub rim: f32,
+    /// how far the rim reaches in from the silhouette: it covers the surface where n·v is below this
+    pub rim_width: f32,
+}
+
+impl Default for Toon {
+    fn default() -> Self {
+        Self {
+            bands: 3,
+            rim: 0.5,
+            rim_width: 0.3,
+        }
+    }
+}
+
+impl Bsdf for Toon {
+    fn evaluate(&self, material: &Material, color: Color, frame: &ShadingFrame, lightv: Vec4, viewv: Vec4) -> Color {
+        let normalv = frame.normalv;
+        let light_dot_normal = lightv.dot(normalv);
+        if light_dot_normal <= 0.0 {
+            return Color::BLACK;
+        }
+
+        let bands = self.bands.max(1) as f32;
+        let diffuse = color * material.diffuse * ((light_dot_normal * bands).ceil() / bands);
+
+        let reflectv = (-lightv).reflect(normalv);
+        let reflect_dot_eye = reflectv.dot(viewv).max(0.0);
+        let specular = if f32::powf(reflect_dot_eye, material.shininess) > 0.5 {
+            Color::WHITE * material.specular
+        } else {
+            Color::BLACK
+        };
+
+        // same scale as `Phong`: diffuse = 1 is a white lambert surface facing the light
+        (diffuse + specular) * (1.0 / PI)
+    }
+
+    fn unlit(&self, frame: &ShadingFrame, viewv: Vec4) -> Color {
+        if frame.normalv.dot(viewv) < self.rim_width {
+            Color::WHITE * self.rim
+        } else {
+            Color::BLACK
+        }
+    }
+}
+
+/// ink lines drawn over a finished image where the depth or the normal seen by neighbouring pixels
+/// jumps: silhouettes against the background or farther objects, and creases where faces meet.
+/// set `World::outline` and every render entry point draws them
+#[derive(Debug, Clone, Copy)]
+pub struct Outline {
+    pub color: Color,
+    /// depth difference, relative to the nearer pixel, that counts as a silhouette
+    pub depth_threshold: f32,
+    /// normals further apart than this make a crease
+    pub crease_angle: Angle,
+}
+
+impl Default for Outline {
+    fn default() -> Self {
+        Self {
+            color: Color::BLACK,
+            depth_threshold: 0.1,
+            crease_angle: Angle::from_degree(45.0),
+        }
+    }
+}
+
+impl Outline {
+    /// draws the lines over `canvas`, rendered from `camera`. each line is one pixel wide and
+    /// lies on the nearer of the two surfaces. `canvas` must be the camera's size: nothing is drawn otherwise
+    pub fn draw(&self, world: &World, camera: &Camera, canvas: &mut Canvas) {
+        if canvas.get_size() != (camera.hsize, camera.vsize) {
+            return;
+        }
+        for (x, y) in self.edges(world, camera, 0..camera.hsize, 0..camera.vsize) {
+            canvas.write_pixel(x, y, self.color);
+        }
+    }
+
+    /// pixels in the `xs` x `ys` region of the camera's image that the lines cover. surfaces are traced
+    /// one pixel beyond the region, so a tile gets the same lines as it would in the whole image
+    pub fn edges(&self, world: &World, camera: &Camera, xs: Range<u32>, ys: Range<u32>) -> Vec<(u32, u32)> {
+        let traced_xs = xs.start.saturating_sub(1)..xs.end.saturating_add(1).min(camera.hsize);
+        let traced_ys = ys.start.saturating_sub(1)..ys.end.saturating_add(1).min(camera.vsize);
+
+        // depth and normal of the first surface seen through each traced pixel
+        let mut surfaces = Vec::with_capacity(traced_xs.len() * traced_ys.len());
+        for y in traced_ys.clone() {
+            for x in traced_xs.clone() {
+                let ray = camera.get_ray(x, y);
+                let surface = get_frontmost_intersection(world.intersect(&ray)).map(|hit| {
+                    let normalv = if hit.normalv.dot(ray.dir) > 0.0 {
+                        -hit.normalv
+                    } else {
+                        hit.normalv
+                    };
+                    (hit.t, normalv)
+                });
+                surfaces.push(surface);
+            }
+        }
+
+        let width = traced_xs.len() as u32;
+        let at = |x: u32, y: u32| surfaces[((x - traced_xs.start) + (y - traced_ys.start) * width) as usize];
+        let mut edges = Vec::new();
+        for y in ys {
+            for x in xs.clone() {
+                let neighbours = [(x.wrapping_sub(1), y), (x + 1, y), (x, y.wrapping_sub(1)), (x, y + 1)];
+                let is_edge = neighbours
+                    .iter()
+                    .filter(|(nx, ny)| traced_xs.contains(nx) && traced_ys.contains(ny))
+                    .any(|&(nx, ny)| self.is_edge(at(x, y), at(nx, ny)));
+                if is_edge {
+                    edges.push((x, y));
+                }
+            }
+        }
+        edges
+    }
+
+    /// whether a line belongs on `surface`, given the surface seen by a neighbouring pixel
+    fn is_edge(&self, surface: Option<(f32, Vec4)>, neighbour: Option<(f32, Vec4)>) -> bool {
+        match (surface, neighbour) {
+            (Some(_), None) => true,
+            (Some((depth, normalv)), Some((neighbour_depth, neighbour_normalv))) => {
+                if depth > neighbour_depth {
+                    return false;
+                }
+                neighbour_depth - depth > self.depth_threshold * depth
+                    || normalv.dot(neighbour_normalv) < self.crease_angle.radian().cos()
+            }
+            _ => false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{point, vector, view_transform, Aabb, PointLight, Ray, Sphere};
+
+    use super::*;
+
+    #[test]
+    fn diffuse_comes_in_bands() {
+        let material = Material {
+            specular: 0.0,
+            ..Default::default()
+        };
+        let toon = Toon {
+            rim: 0.0,
+            ..Default::default()
+        };
+        let frame = ShadingFrame::new(vector(0.0, 1.0, 0.0), vector(1.0, 0.0, 0.0));
+        let viewv = vector(0.0, 1.0, 0.0);
+        let shade = |angle: f32| {
+            let lightv = vector(angle.to_radians().sin(), angle.to_radians().cos(), 0.0);
+            toon.evaluate(&material, Color::WHITE, &frame, lightv, viewv).red * PI
+        };
+
+        // cos 10° and cos 40° are both in the top band, cos 60° = 0.5 in the middle one
+        assert_eq!(shade(10.0), shade(40.0));
+        assert_eq!(shade(10.0), material.diffuse);
+        assert!((shade(60.0) - material.diffuse * 2.0 / 3.0).abs() < 1e-5);
+        assert_eq!(shade(100.0), 0.0);
+    }
+
+    #[test]
+    fn rim_lights_the_silhouette() {
+        let frame = ShadingFrame::new(vector(0.0, 1.0, 0.0), vector(1.0, 0.0, 0.0));
+        let toon = Toon::default();
+        assert_eq!(toon.unlit(&frame, vector(0.0, 0.0, -1.0)).red, toon.rim);
+        assert_eq!(toon.unlit(&frame, vector(0.0, 1.0, 0.0)).red, 0.0);
+    }
+
+    #[test]
+    fn rim_does_not_depend_on_lights() {
+        // the camera sees the rim of a sphere lit from behind, then with a second light
+        let mut sphere = Sphere::new(point(0.0, 0.0, 0.0), 1.0);
+        sphere.mat.ambient = 0.0;
+        sphere.mat.bsdf = Box::new(Toon::default());
+        let mut world = World::new();
+        world.add_object(sphere);
+        let ray = Ray::new(point(0.99, 0.0, -5.0), vector(0.0, 0.0, 1.0));
+
+        world.add_light(PointLight::new(point(0.0, 0.0, 10.0), Color::WHITE));
+        let one = world.shade(&ray);
+        world.add_light(PointLight::new(point(0.0, 0.0, 20.0), Color::WHITE));
+        assert_eq!(world.shade(&ray).red, one.red);
+        assert_eq!(one.red, Toon::default().rim);
+    }
+
+    fn outlined(world: &World, from: Vec4, to: Vec4) -> Canvas {
+        let view_mat = view_transform(from, to, vector(0.0, 1.0, 0.0));
+        let camera = Camera::new(21, 21, Angle::from_degree(60.0), 1.0, view_mat);
+        let mut canvas = Canvas::new(21, 21, None);
+        let outline = Outline {
+            color: Color::new(1.0, 0.0, 0.0),
+            ..Default::default()
+        };
+        outline.draw(world, &camera, &mut canvas);
+        canvas
+    }
+
+    #[test]
+    fn outline_traces_silhouette() {
+        let mut world = World::new();
+        world.add_object(Sphere::new(point(0.0, 0.0, 0.0), 1.0));
+        let canvas = outlined(&world, point(0.0, 0.0, -5.0), point(0.0, 0.0, 0.0));
+
+        let red = |x, y| canvas.pixel_at(x, y).red == 1.0;
+        // the row through the center crosses the outline once on each side, and nothing inside the smooth sphere
+        let row: Vec<u32> = (0..21).filter(|&x| red(x, 10)).collect();
+        assert_eq!(row.len(), 2);
+        assert_eq!(row[0] + row[1], 20);
+        assert!(!red(10, 10) && !red(9, 9));
+    }
+
+    #[test]
+    fn outline_finds_creases() {
+        // a box seen edge on: its vertical edge is a crease between two faces at the same depth
+        let mut world = World::new();
+        world.add_object(Aabb::new(point(-1.0, -1.0, -1.0), point(1.0, 1.0, 1.0), true));
+        let canvas = outlined(&world, point(4.0, 0.0, -4.0), point(1.0, 0.0, -1.0));
+
+        let red = |x, y| canvas.pixel_at(x, y).red == 1.0;
+        assert!(red(9, 10) || red(10, 10) || red(11, 10));
+        assert!(!red(7, 10) && !red(13, 10));
+    }
+
+    #[test]
+    fn tile_edges_match_the_whole_image() {
+        let mut world = World::new();
+        world.add_object(Sphere::new(point(0.0, 0.0, 0.0), 1.0));
+        let view_mat = view_transform(point(0.0, 0.0, -5.0), point(0.0, 0.0, 0.0), vector(0.0, 1.0, 0.0));
+        let camera = Camera::new(21, 21, Angle::from_degree(60.0), 1.0, view_mat);
+        let outline = Outline::default();
+
+        let whole = outline.edges(&world, &camera, 0..21, 0..21);
+        let tile = outline.edges(&world, &camera, 4..10, 7..15);
+        let expected: Vec<_> = whole
+            .into_iter()
+            .filter(|(x, y)| (4..10).contains(x) && (7..15).contains(y))
+            .collect();
+        assert!(!tile.is_empty());
+        assert_eq!(tile, expected);
+    }
+}
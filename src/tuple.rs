@@ -23,9 +23,9 @@ impl<const N: usize> Tuple<N> {
 
     pub fn normalize(&self) -> Self {
         let mag = self.mag();
-        let mut values = [0.0; N];
-        for n in 0..N {
-            values[n] = self.values[n] / mag;
+        let mut values = self.values;
+        for v in values.iter_mut() {
+            *v /= mag;
         }
         Self { values }
     }
@@ -0,0 +1,169 @@
+use std::f32::consts::PI;
+
+use crate::Vec4;
+
+/// (u, v) in [0, 1). u goes around / across, v goes up
+pub type Uv = (f32, f32);
+
+/// longitude around the y axis. u = 0.5 faces -z (toward the default camera), the seam is at +z
+fn longitude(p: Vec4) -> f32 {
+    let theta = p[0].atan2(-p[2]);
+    (theta / (2.0 * PI) + 0.5).rem_euclid(1.0)
+}
+
+/// point on the unit sphere -> longitude, latitude
+pub fn spherical_map(p: Vec4) -> Uv {
+    let radius = (p[0] * p[0] + p[1] * p[1] + p[2] * p[2]).sqrt();
+    let phi = (p[1] / radius).clamp(-1.0, 1.0).acos();
+    (longitude(p), 1.0 - phi / PI)
+}
+
+/// xz plane, repeating every unit
+pub fn planar_map(p: Vec4) -> Uv {
+    (p[0].rem_euclid(1.0), p[2].rem_euclid(1.0))
+}
+
+/// around the y axis like `spherical_map`, v repeats every unit of height
+pub fn cylindrical_map(p: Vec4) -> Uv {
+    (longitude(p), p[1].rem_euclid(1.0))
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CubeFace {
+    Left,
+    Right,
+    Front,
+    Back,
+    Up,
+    Down,
+}
+
+/// point on the cube [-1, 1]^3 -> face it lies on and uv within that face.
+/// faces are unfolded around the front (-z) face, so neighbouring faces share their edges
+/// the way `UvMapping::Cube` lays them out
+pub fn cube_map(p: Vec4) -> (CubeFace, Uv) {
+    let (x, y, z) = (p[0], p[1], p[2]);
+    let coord = |a: f32| ((a + 1.0) / 2.0).clamp(0.0, 1.0);
+
+    let (ax, ay, az) = (x.abs(), y.abs(), z.abs());
+    if ax >= ay && ax >= az {
+        if x > 0.0 {
+            (CubeFace::Right, (coord(z), coord(y)))
+        } else {
+            (CubeFace::Left, (coord(-z), coord(y)))
+        }
+    } else if ay >= az {
+        if y > 0.0 {
+            (CubeFace::Up, (coord(x), coord(z)))
+        } else {
+            (CubeFace::Down, (coord(x), coord(-z)))
+        }
+    } else if z > 0.0 {
+        (CubeFace::Back, (coord(-x), coord(y)))
+    } else {
+        (CubeFace::Front, (coord(x), coord(y)))
+    }
+}
+
+/// how a texture finds its uv from an object space point
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UvMapping {
+    /// uv reported by the surface (`Intersection::uv`).
+    /// where no surface uv is known (inside another pattern) this acts as `Planar`
+    Surface,
+    Spherical,
+    Planar,
+    Cylindrical,
+    /// all six faces from one image laid out as a horizontal cross:
+    /// ```text
+    ///     up
+    /// left front right back
+    ///     down
+    /// ```
+    Cube,
+}
+
+impl UvMapping {
+    pub fn map(&self, p: Vec4) -> Uv {
+        match self {
+            UvMapping::Surface | UvMapping::Planar => planar_map(p),
+            UvMapping::Spherical => spherical_map(p),
+            UvMapping::Cylindrical => cylindrical_map(p),
+            UvMapping::Cube => {
+                let (face, (u, v)) = cube_map(p);
+                let (col, row) = match face {
+                    CubeFace::Up => (1.0, 0.0),
+                    CubeFace::Left => (0.0, 1.0),
+                    CubeFace::Front => (1.0, 1.0),
+                    CubeFace::Right => (2.0, 1.0),
+                    CubeFace::Back => (3.0, 1.0),
+                    CubeFace::Down => (1.0, 2.0),
+                };
+                ((col + u) / 4.0, (2.0 - row + v) / 3.0)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{lib_test::assert_almost_eq_f32, point};
+
+    use super::*;
+
+    fn assert_uv(actual: Uv, expected: Uv) {
+        assert_almost_eq_f32(actual.0, expected.0);
+        assert_almost_eq_f32(actual.1, expected.1);
+    }
+
+    #[test]
+    fn spherical() {
+        assert_uv(spherical_map(point(0.0, 0.0, -1.0)), (0.5, 0.5));
+        assert_uv(spherical_map(point(1.0, 0.0, 0.0)), (0.75, 0.5));
+        assert_uv(spherical_map(point(0.0, 0.0, 1.0)), (0.0, 0.5));
+        assert_uv(spherical_map(point(-1.0, 0.0, 0.0)), (0.25, 0.5));
+        assert_almost_eq_f32(spherical_map(point(0.0, 1.0, 0.0)).1, 1.0);
+        assert_almost_eq_f32(spherical_map(point(0.0, -1.0, 0.0)).1, 0.0);
+        let s = 2.0_f32.sqrt() / 2.0;
+        assert_uv(spherical_map(point(s, s, 0.0)), (0.75, 0.75));
+    }
+
+    #[test]
+    fn planar() {
+        assert_uv(planar_map(point(0.25, 0.0, 0.5)), (0.25, 0.5));
+        assert_uv(planar_map(point(0.25, 5.0, -0.25)), (0.25, 0.75));
+        assert_uv(planar_map(point(-1.75, 0.0, 3.5)), (0.25, 0.5));
+    }
+
+    #[test]
+    fn cylindrical() {
+        assert_uv(cylindrical_map(point(0.0, 0.0, -1.0)), (0.5, 0.0));
+        assert_uv(cylindrical_map(point(0.0, 0.5, -1.0)), (0.5, 0.5));
+        assert_uv(cylindrical_map(point(1.0, 1.25, 0.0)), (0.75, 0.25));
+        assert_uv(cylindrical_map(point(-1.0, -0.25, 0.0)), (0.25, 0.75));
+    }
+
+    #[test]
+    fn cube_faces() {
+        assert_eq!(cube_map(point(-1.0, 0.5, -0.25)).0, CubeFace::Left);
+        assert_eq!(cube_map(point(1.1, -0.75, 0.8)).0, CubeFace::Right);
+        assert_eq!(cube_map(point(0.1, 0.6, 0.9)).0, CubeFace::Back);
+        assert_eq!(cube_map(point(-0.7, 0.0, -2.0)).0, CubeFace::Front);
+        assert_eq!(cube_map(point(0.5, 1.0, 0.9)).0, CubeFace::Up);
+        assert_eq!(cube_map(point(-0.2, -1.3, 1.1)).0, CubeFace::Down);
+
+        assert_uv(cube_map(point(-0.5, 0.5, -1.0)).1, (0.25, 0.75));
+        assert_uv(cube_map(point(0.5, -0.5, -1.0)).1, (0.75, 0.25));
+        assert_uv(cube_map(point(1.0, 0.5, -0.5)).1, (0.25, 0.75));
+        assert_uv(cube_map(point(-0.5, 1.0, -0.5)).1, (0.25, 0.25));
+        assert_uv(cube_map(point(-0.5, -1.0, 0.5)).1, (0.25, 0.25));
+    }
+
+    #[test]
+    fn cube_cross_layout() {
+        // center of the front face is the center of the cross
+        assert_uv(UvMapping::Cube.map(point(0.0, 0.0, -1.0)), (0.375, 0.5));
+        assert_uv(UvMapping::Cube.map(point(0.0, 1.0, 0.0)), (0.375, 5.0 / 6.0));
+        assert_uv(UvMapping::Cube.map(point(0.0, 0.0, 1.0)), (0.875, 0.5));
+    }
+}
@@ -1,103 +1,346 @@
 use crate::{
-    point, point_lighting, Color, Intersection, Material, MaterialPattern, Object, PointLight, Ray, Sphere, Vec4,
+    keep_channel, point, point_lighting, refractive_indices, schlick, spectrum_to_color, Color, Intersection, Light,
+    Material, Object, Outline, PointLight, Ray, RenderStats, Rng, ShadingFrame, Sphere, StatsCollector, Subsurface,
+    Vec4, RGB_WAVELENGTHS,
 };
 
+/// offset along the normal for rays leaving a surface, so they do not hit the surface they start on
+pub const SURFACE_EPSILON: f32 = 0.001;
+
 pub struct World {
     pub objects: Vec<Box<dyn Object>>,
-    pub point_lights: Vec<PointLight>,
+    pub lights: Vec<Box<dyn Light>>,
+    /// how many times a ray may bounce off reflective surfaces
+    pub max_depth: u32,
+    /// 0 renders in rgb. otherwise every camera ray is traced at this many wavelengths, with materials
+    /// and lights evaluated as spectra, and the result turned into a color through cie xyz
+    pub spectral_samples: u32,
+    /// 0 lights surfaces with `lights` only. otherwise each hit also gathers light bounced off (or emitted by)
+    /// other surfaces along this many rays drawn from its bsdf, so emissive objects light the scene
+    pub indirect_samples: u32,
+    /// ink lines drawn over the finished image (see `Outline`)
+    pub outline: Option<Outline>,
+    stats: StatsCollector,
 }
 
 impl World {
     pub fn new() -> Self {
         Self {
             objects: Vec::new(),
-            point_lights: Vec::new(),
+            lights: Vec::new(),
+            max_depth: 5,
+            spectral_samples: 0,
+            indirect_samples: 0,
+            outline: None,
+            stats: StatsCollector::default(),
         }
     }
 
+    /// starts or stops collecting `RenderStats`. off by default, as timing every ray has a cost
+    pub fn enable_stats(&self, enabled: bool) {
+        self.stats.set_enabled(enabled);
+    }
+
+    /// stats collected so far. resets the counters
+    pub fn take_stats(&self) -> RenderStats {
+        self.stats.take()
+    }
+
     pub fn add_object<O: Object + 'static>(&mut self, obj: O) {
         self.objects.push(Box::new(obj));
     }
 
-    pub fn add_pointlight(&mut self, light: PointLight) {
-        self.point_lights.push(light);
+    pub fn add_light<L: Light + 'static>(&mut self, light: L) {
+        self.lights.push(Box::new(light));
     }
 
-    pub fn intersect(&self, ray: &Ray) -> Vec<Intersection> {
+    pub fn intersect(&self, ray: &Ray) -> Vec<Intersection<'_>> {
+        let start = self.stats.start_timer();
+
         let mut intersections = Vec::new();
         for obj in &self.objects {
             let mut cur_intersections = obj.ray_intersect(ray);
             intersections.append(&mut cur_intersections);
         }
 
-        intersections.sort_by(|a, b| a.t.partial_cmp(&b.t).unwrap());
+        intersections.sort_by(|a, b| a.t.total_cmp(&b.t));
+
+        self.stats.record(|stats| {
+            for obj in &self.objects {
+                *stats.intersection_tests.entry(obj.name()).or_insert(0) += 1;
+            }
+            stats.peak_intersections = stats.peak_intersections.max(intersections.len());
+            if let Some(start) = start {
+                stats.traversal_time += start.elapsed();
+            }
+        });
+
         intersections
     }
 
-    pub fn shade(&self, ray: &Ray, eyev: Vec4) -> Color {
+    /// color seen along `ray`. the eye vector for specular highlights is the ray's own direction,
+    /// so it is correct for every camera pixel and for reflected / refracted rays
+    pub fn shade(&self, ray: &Ray) -> Color {
+        let start = self.stats.start_timer();
+        self.stats.record(|stats| stats.primary_rays += 1);
+
+        let color = if self.spectral_samples > 0 && ray.wavelength.is_none() {
+            // shading at a single wavelength gives the same value in every channel
+            spectrum_to_color(self.spectral_samples, |wavelength| {
+                self.shade_ray(&ray.at_wavelength(wavelength), self.max_depth)
+                    .luminance()
+            })
+        } else {
+            self.shade_ray(ray, self.max_depth)
+        };
+
+        self.stats.add_shade_time(start);
+        color
+    }
+
+    /// `remaining`: how many more reflected / refracted rays may be spawned from here
+    fn shade_ray(&self, ray: &Ray, remaining: u32) -> Color {
         let intersections = self.intersect(ray);
 
-        if intersections.len() == 0 {
+        match intersections.iter().position(|i| i.t > 0.0) {
+            Some(hit) => self.shade_hit(&intersections, hit, ray, remaining),
+            None => Color::BLACK,
+        }
+    }
+
+    fn shade_hit(&self, intersections: &[Intersection], hit: usize, ray: &Ray, remaining: u32) -> Color {
+        let material = intersections[hit].material;
+        if ray.wavelength.is_none() && material.dispersion.is_some() && material.transparency > 0.0 {
+            return self.shade_dispersed(intersections, hit, ray, remaining);
+        }
+
+        let (n1, n2) = refractive_indices(intersections, hit, ray.wavelength);
+        let hit = &intersections[hit];
+
+        // normal on the side the ray came from (inside of a sphere, ...)
+        let inside = hit.normalv.dot(ray.dir) > 0.0;
+        let side = if inside { -1.0 } else { 1.0 };
+
+        // offsets (and so shadow rays) use the geometric normal, lighting the bumped one
+        let over_pos = hit.pos + hit.normalv * (side * SURFACE_EPSILON);
+        let under_pos = hit.pos - hit.normalv * (side * SURFACE_EPSILON);
+        let normalv = material.shading_normal(hit) * side;
+        let frame = ShadingFrame::new(normalv, hit.tangent);
+
+        // in spectral mode colors are reflectances / intensities at the ray's wavelength, gray
+        let spectral = ray.wavelength.filter(|_| self.spectral_samples > 0);
+        let (color, emitted) = match spectral {
+            Some(wavelength) => (
+                Color::WHITE * material.reflectance_at(hit.object_pos, hit.uv, wavelength),
+                Color::WHITE * material.emitted_at(wavelength),
+            ),
+            None => (material.color(hit.object_pos, hit.uv), material.emitted()),
+        };
+
+        // with subsurface scattering the diffuse light comes out of the surrounding surface instead: the
+        // shading model sees a black base color, so it only adds its specular part
+        let bsdf_color = if material.subsurface.is_some() {
             Color::BLACK
         } else {
-            let front_most = intersections.iter().filter(|i| i.t > 0.0).next();
-            if let Some(front_most) = front_most {
-                //let mut acc_color = Color::BLACK;
-                let mut acc_color = front_most.material.color(front_most.pos) * front_most.material.ambient;
-                for light in &self.point_lights {
-                    let is_shadowed = self.is_shadowed(front_most.pos, light);
-                    acc_color += point_lighting(
-                        front_most.material,
-                        light,
-                        front_most.pos,
-                        eyev,
-                        front_most.normalv,
-                        is_shadowed,
-                    );
-                }
-                acc_color
-            } else {
-                Color::BLACK
+            color
+        };
+
+        // emissive surfaces look self-lit. they light other objects through `indirect_samples`
+        let mut surface = color * material.ambient + emitted + material.bsdf.unlit(&frame, -ray.dir);
+        for light in &self.lights {
+            let sample = light.illuminate(hit.pos, spectral);
+            if sample.intensity.luminance() <= 0.0 {
+                // outside a spot light's cone, ...: no need for a shadow ray
+                continue;
             }
+            let is_shadowed = self.is_shadowed(over_pos, light.as_ref());
+            surface += point_lighting(material, bsdf_color, &sample, ray.dir, &frame, is_shadowed);
+        }
+        if self.indirect_samples > 0 && remaining > 0 {
+            surface += self.indirect_color(material, bsdf_color, &frame, over_pos, ray);
+        }
+        if let (Some(subsurface), false) = (&material.subsurface, inside) {
+            surface += self.subsurface_color(hit, subsurface, ray, spectral);
         }
-    }
 
-    pub fn is_shadowed(&self, pos: Vec4, light: &PointLight) -> bool {
-        let obj_to_light = light.pos - pos;
-        let obj_to_light_v = obj_to_light.normalize();
-        let ray = Ray::new(pos, obj_to_light_v);
-        let intersections = self.intersect(&ray);
+        if let Some(film) = material.thin_film {
+            let reflectance = film.reflectance_color(-ray.dir.dot(normalv), n1, n2, ray.wavelength);
+            let reflected = self.reflected_color(ray, 1.0, normalv, over_pos, remaining);
+            let refracted = self.refracted_color(ray, material, normalv, under_pos, (n1, n2), remaining);
+            return surface + reflected * reflectance + refracted * (Color::WHITE - reflectance);
+        }
+
+        // a clear coat also mirrors the scene by its fresnel, on top of what the base reflects
+        let coat = material.clearcoat.map_or(0.0, |coat| coat.fresnel(-ray.dir, normalv));
+        let reflective = material.reflective + coat * (1.0 - material.reflective);
+
+        let reflected = self.reflected_color(ray, reflective, normalv, over_pos, remaining);
+        let refracted = self.refracted_color(ray, material, normalv, under_pos, (n1, n2), remaining);
 
-        if intersections.len() == 0 {
-            false
+        if material.reflective > 0.0 && material.transparency > 0.0 {
+            let reflectance = schlick(ray.dir, normalv, n1, n2);
+            surface + reflected * reflectance + refracted * (1.0 - reflectance)
         } else {
-            if let Some(_) = intersections
+            surface + reflected + refracted
+        }
+    }
+
+    /// light arriving from other surfaces, estimated from `indirect_samples` rays importance sampled by the
+    /// material's bsdf. those rays are shaded with no depth left, so they gather no further: light bounces once
+    fn indirect_color(
+        &self,
+        material: &Material,
+        color: Color,
+        frame: &ShadingFrame,
+        over_pos: Vec4,
+        ray: &Ray,
+    ) -> Color {
+        // seeded by the position, so a point is shaded the same whichever thread renders it
+        let seed = (0..3).fold(0u64, |seed, i| {
+            (seed ^ u64::from(over_pos[i].to_bits())).wrapping_mul(0x0100_0000_01B3)
+        });
+        let mut rng = Rng::new(seed);
+
+        let mut sum = Color::BLACK;
+        for _ in 0..self.indirect_samples {
+            let u = (rng.next_f32(), rng.next_f32());
+            if let Some(sample) = material.bsdf.sample(material, color, frame, -ray.dir, u) {
+                sum += self.shade_ray(&ray.spawn(over_pos, sample.lightv), 0) * sample.weight;
+            }
+        }
+        sum * (1.0 / self.indirect_samples as f32)
+    }
+
+    /// light that entered the object's surface around `hit` and comes out at it. gathered from points
+    /// found by probing the surface at `Subsurface::sample` offsets in the tangent plane
+    fn subsurface_color(&self, hit: &Intersection, subsurface: &Subsurface, ray: &Ray, spectral: Option<f32>) -> Color {
+        let subsurface = match spectral {
+            Some(wavelength) => subsurface.at_wavelength(wavelength),
+            None => *subsurface,
+        };
+        let eta = subsurface.refractive_index;
+        let frame = ShadingFrame::new(hit.normalv, hit.tangent);
+
+        let mut sum = Color::BLACK;
+        for i in 0..subsurface.samples.get() {
+            let (r, angle, pdf) = subsurface.sample(i);
+            let offset = frame.tangent * (r * angle.cos()) + frame.bitangent * (r * angle.sin());
+            // straight down from above the tangent plane, onto the front of the same object
+            let probe = Ray::new(hit.pos + offset + hit.normalv * r, -hit.normalv);
+            let intersections = self.intersect(&probe);
+            let entry = intersections
                 .iter()
-                .filter(|i| i.t > 0.01 && i.t <= obj_to_light.mag() && i.normalv.dot(obj_to_light_v) > 0.0)
-                .next()
-            {
-                true
-            } else {
-                false
+                .filter(|i| i.t > 0.0 && std::ptr::eq(i.material, hit.material) && i.normalv.dot(hit.normalv) > 0.0)
+                .min_by(|a, b| (a.t - r).abs().total_cmp(&(b.t - r).abs()));
+            let entry = match entry {
+                Some(entry) => entry,
+                None => continue,
+            };
+
+            let entry_over = entry.pos + entry.normalv * SURFACE_EPSILON;
+            let mut irradiance = Color::BLACK;
+            for light in &self.lights {
+                let sample = light.illuminate(entry.pos, spectral);
+                let cos = sample.lightv.dot(entry.normalv);
+                if cos <= 0.0 || sample.intensity.luminance() <= 0.0 || self.is_shadowed(entry_over, light.as_ref()) {
+                    continue;
+                }
+                let transmitted = 1.0 - schlick(-sample.lightv, entry.normalv, 1.0, eta);
+                irradiance += sample.intensity * (cos * transmitted);
             }
+
+            let distance = (entry.pos - hit.pos).mag();
+            sum += subsurface.profile(distance) * irradiance * (1.0 / pdf);
         }
+
+        let transmitted = 1.0 - schlick(ray.dir, hit.normalv, 1.0, eta);
+        sum * (transmitted / subsurface.samples.get() as f32)
+    }
+
+    /// white light entering a dispersive material splits up: each channel is traced on
+    /// at its own wavelength, and so its own refractive index
+    fn shade_dispersed(&self, intersections: &[Intersection], hit: usize, ray: &Ray, remaining: u32) -> Color {
+        let mut color = Color::BLACK;
+        for (channel, wavelength) in RGB_WAVELENGTHS.iter().enumerate() {
+            let single = ray.at_wavelength(*wavelength);
+            color += keep_channel(self.shade_hit(intersections, hit, &single, remaining), channel);
+        }
+        color
+    }
+
+    /// `reflective`: share of the mirrored light (see `Material::reflective`)
+    fn reflected_color(&self, ray: &Ray, reflective: f32, normalv: Vec4, over_pos: Vec4, remaining: u32) -> Color {
+        if remaining == 0 || reflective <= 0.0 {
+            return Color::BLACK;
+        }
+
+        self.stats.record(|stats| stats.secondary_rays += 1);
+
+        let reflectv = ray.dir.reflect(normalv);
+        let reflect_ray = ray.spawn(over_pos, reflectv);
+        self.shade_ray(&reflect_ray, remaining - 1) * reflective
+    }
+
+    /// `(n1, n2)`: refractive index on the incoming and the outgoing side
+    fn refracted_color(
+        &self,
+        ray: &Ray,
+        material: &Material,
+        normalv: Vec4,
+        under_pos: Vec4,
+        (n1, n2): (f32, f32),
+        remaining: u32,
+    ) -> Color {
+        if remaining == 0 || material.transparency <= 0.0 {
+            return Color::BLACK;
+        }
+
+        // snell's law
+        let n_ratio = n1 / n2;
+        let cos_i = -ray.dir.dot(normalv);
+        let sin2_t = n_ratio * n_ratio * (1.0 - cos_i * cos_i);
+        if sin2_t > 1.0 {
+            // total internal reflection, all light goes to reflected_color
+            return Color::BLACK;
+        }
+
+        self.stats.record(|stats| stats.secondary_rays += 1);
+
+        let cos_t = (1.0 - sin2_t).sqrt();
+        let dir = normalv * (n_ratio * cos_i - cos_t) + ray.dir * n_ratio;
+        let refract_ray = ray.spawn(under_pos, dir);
+        self.shade_ray(&refract_ray, remaining - 1) * material.transparency
+    }
+
+    /// whether something lies between `pos` and `light`, along `LightSample::lightv` up to its distance.
+    /// `pos` should already be lifted off the surface (see SURFACE_EPSILON)
+    pub fn is_shadowed(&self, pos: Vec4, light: &dyn Light) -> bool {
+        self.stats.record(|stats| stats.shadow_rays += 1);
+
+        let sample = light.illuminate(pos, None);
+        let ray = Ray::new(pos, sample.lightv);
+        let intersections = self.intersect(&ray);
+
+        intersections
+            .iter()
+            .any(|i| i.t > 0.0 && i.t <= sample.distance && i.normalv.dot(sample.lightv) > 0.0)
     }
 }
 
 impl Default for World {
     fn default() -> Self {
         let mut obj1 = Sphere::new(point(0.0, 0.0, 0.0), 1.0);
-        obj1.mat = Material::new(Color::new(0.8, 0.2, 0.6), MaterialPattern::Solid, 0.2, 0.7, 0.2, 200.0);
+        obj1.mat = Material::new(Color::new(0.8, 0.2, 0.6), 0.2, 0.7, 0.2, 200.0);
 
         let mut obj2 = Sphere::new(point(0.0, 0.0, 0.0), 0.5);
-        obj2.mat = Material::new(Color::new(0.2, 0.6, 0.8), MaterialPattern::Solid, 0.2, 0.7, 0.2, 200.0);
+        obj2.mat = Material::new(Color::new(0.2, 0.6, 0.8), 0.2, 0.7, 0.2, 200.0);
 
         let light = PointLight::new(point(-10.0, 10.0, -10.0), Color::WHITE);
 
         let mut world = World::new();
         world.add_object(obj1);
         world.add_object(obj2);
-        world.add_pointlight(light);
+        world.add_light(light);
 
         world
     }
@@ -107,7 +350,8 @@ impl Default for World {
 mod tests {
     use crate::{
         lib_test::{assert_almost_eq_color, assert_almost_eq_f32},
-        vector, Ray,
+        vector, Aabb, Angle, Bsdf, BumpMap, ClearCoat, DirectionalLight, Dispersion, NormalMapping, Phong, Ray,
+        SampledSpectrum, SpotLight, Subsurface, ThinFilm, Toon,
     };
 
     use super::*;
@@ -116,14 +360,14 @@ mod tests {
     fn create_world() {
         let world = World::new();
         assert_eq!(world.objects.len(), 0);
-        assert_eq!(world.point_lights.len(), 0);
+        assert_eq!(world.lights.len(), 0);
     }
 
     #[test]
     fn default_world() {
         let world = World::default();
         assert_eq!(world.objects.len(), 2);
-        assert_eq!(world.point_lights.len(), 1);
+        assert_eq!(world.lights.len(), 1);
     }
 
     #[test]
@@ -143,16 +387,459 @@ mod tests {
     fn shade_world_with_ray() {
         let world = World::default();
         let ray = Ray::new(point(0.0, 0.0, -5.0), vector(0.0, 0.0, 1.0));
-        let color = world.shade(&ray, vector(0.0, 0.0, 1.0));
+        let color = world.shade(&ray);
         assert_almost_eq_color(color, Color::new(0.46066123, 0.11516531, 0.34549594));
     }
 
+    #[test]
+    fn highlight_off_camera_axis() {
+        // light at the eye, ray hits an off-axis sphere straight on: full diffuse and specular
+        let mut world = World::new();
+        world.add_object(Sphere::new(point(4.0, 0.0, 5.0), 1.0));
+        world.add_light(PointLight::new(point(0.0, 0.0, 0.0), Color::WHITE));
+
+        let ray = Ray::new(point(0.0, 0.0, 0.0), vector(4.0, 0.0, 5.0).normalize());
+        assert_almost_eq_color(world.shade(&ray), Color::new(1.9, 1.9, 1.9));
+    }
+
+    #[test]
+    fn spectral_matches_rgb() {
+        let mut world = World::default();
+        let ray = Ray::new(point(0.0, 0.0, -5.0), vector(0.0, 0.0, 1.0));
+        let rgb = world.shade(&ray);
+        world.spectral_samples = 32;
+        let spectral = world.shade(&ray);
+        for (a, b) in [
+            (spectral.red, rgb.red),
+            (spectral.green, rgb.green),
+            (spectral.blue, rgb.blue),
+        ] {
+            assert!((a - b).abs() < 0.02, "{:?} vs {:?}", spectral, rgb);
+        }
+    }
+
+    #[test]
+    fn sodium_lamp_turns_blue_black() {
+        let lamp_world = |color: Color| {
+            let mut ball = Sphere::new(point(0.0, 0.0, 0.0), 1.0);
+            ball.mat.color = color;
+            ball.mat.ambient = 0.0;
+            ball.mat.specular = 0.0;
+
+            // low pressure sodium: almost all light at 589 nm
+            let mut lamp = PointLight::new(point(0.0, 0.0, -10.0), Color::WHITE);
+            lamp.spectrum = Some(Box::new(SampledSpectrum::new(585.0, 4.0, vec![0.0, 50.0, 0.0])));
+
+            let mut world = World::new();
+            world.add_object(ball);
+            world.add_light(lamp);
+            world.spectral_samples = 170;
+            world
+        };
+
+        let ray = Ray::new(point(0.0, 0.0, -5.0), vector(0.0, 0.0, 1.0));
+        let blue = lamp_world(Color::new(0.0, 0.1, 0.9)).shade(&ray);
+        let yellow = lamp_world(Color::new(0.9, 0.8, 0.0)).shade(&ray);
+        assert!(yellow.luminance() > 10.0 * blue.luminance(), "{:?} {:?}", yellow, blue);
+        // and everything looks orange
+        assert!(yellow.red > yellow.green && yellow.green > yellow.blue);
+    }
+
+    #[test]
+    fn custom_bsdf_plugs_in() {
+        /// scatters half of the light evenly, whatever the angle
+        #[derive(Debug)]
+        struct Flat;
+
+        impl Bsdf for Flat {
+            fn evaluate(&self, _: &Material, color: Color, _: &ShadingFrame, _: Vec4, _: Vec4) -> Color {
+                color * (0.5 / std::f32::consts::PI)
+            }
+        }
+
+        let mut ball = Sphere::new(point(0.0, 0.0, 0.0), 1.0);
+        ball.mat.ambient = 0.0;
+        ball.mat.color = Color::new(1.0, 0.5, 0.0);
+        ball.mat.bsdf = Box::new(Flat);
+
+        let mut world = World::new();
+        world.add_object(ball);
+        world.add_light(PointLight::new(point(-10.0, 10.0, -10.0), Color::WHITE));
+
+        let ray = Ray::new(point(0.0, 0.0, -5.0), vector(0.0, 0.0, 1.0));
+        assert_almost_eq_color(world.shade(&ray), Color::new(0.5, 0.25, 0.0));
+    }
+
     #[test]
     fn is_shadowed() {
         let world = World::default();
-        let light = &world.point_lights[0]; // -10, 10, -10,
-        assert_eq!(world.is_shadowed(point(10.0, -10.0, 10.0), light), true);
-        assert_eq!(world.is_shadowed(point(-20.0, 20.0, -20.0), light), false);
-        assert_eq!(world.is_shadowed(point(-2.0, 2.0, -2.0), light), false);
+        let light = world.lights[0].as_ref(); // -10, 10, -10,
+        assert!(world.is_shadowed(point(10.0, -10.0, 10.0), light));
+        assert!(!world.is_shadowed(point(-20.0, 20.0, -20.0), light));
+        assert!(!world.is_shadowed(point(-2.0, 2.0, -2.0), light));
+    }
+
+    #[test]
+    fn directional_light_shadows_reach_infinity() {
+        let mut world = World::new();
+        world.add_object(Sphere::new(point(0.0, 100.0, 0.0), 1.0));
+        world.add_light(PointLight::new(point(0.0, 10.0, 0.0), Color::WHITE));
+        world.add_light(DirectionalLight::new(vector(0.0, -1.0, 0.0), Color::WHITE));
+
+        // the sphere is beyond the point light, but between the ground and the sun
+        assert!(!world.is_shadowed(point(0.0, 0.0, 0.0), world.lights[0].as_ref()));
+        assert!(world.is_shadowed(point(0.0, 0.0, 0.0), world.lights[1].as_ref()));
+        assert!(!world.is_shadowed(point(5.0, 0.0, 0.0), world.lights[1].as_ref()));
+    }
+
+    #[test]
+    fn spot_light_shadows_stop_at_the_light() {
+        let mut world = World::new();
+        // above the spot, so it casts no shadow
+        world.add_object(Sphere::new(point(0.0, 20.0, 0.0), 1.0));
+        world.add_light(SpotLight::new(
+            point(0.0, 10.0, 0.0),
+            vector(0.0, -1.0, 0.0),
+            Angle::from_degree(10.0),
+            Angle::from_degree(15.0),
+            Color::WHITE,
+        ));
+        world.enable_stats(true);
+
+        assert!(!world.is_shadowed(point(0.0, 0.0, 0.0), world.lights[0].as_ref()));
+        let mut floor = Aabb::new(point(-10.0, -1.0, -10.0), point(10.0, 0.0, 10.0), true);
+        floor.mat.ambient = 0.0;
+        world.add_object(floor);
+        world.take_stats();
+
+        let down = vector(0.0, -1.0, 0.0);
+        assert!(world.shade(&Ray::new(point(0.0, 1.0, 0.0), down)).red > 0.5);
+        // outside the cone: dark, and not worth a shadow ray
+        assert_eq!(world.shade(&Ray::new(point(5.0, 1.0, 0.0), down)).red, 0.0);
+        assert_eq!(world.take_stats().shadow_rays, 1);
+    }
+
+    #[test]
+    fn directional_light_shades_like_a_distant_point_light() {
+        let shade = |light: Box<dyn Light>| {
+            let mut world = World::new();
+            world.add_object(Sphere::new(point(0.0, 0.0, 0.0), 1.0));
+            world.lights.push(light);
+            world.shade(&Ray::new(point(0.0, 0.0, -5.0), vector(0.0, 0.0, 1.0)))
+        };
+        let far = shade(Box::new(PointLight::new(point(-1e5, 1e5, -1e5), Color::WHITE)));
+        let sun = shade(Box::new(DirectionalLight::new(vector(1.0, -1.0, 1.0), Color::WHITE)));
+        assert_almost_eq_color(sun, far);
+    }
+
+    #[test]
+    fn emissive_surface_is_self_lit() {
+        let mut lamp = Sphere::new(point(0.0, 0.0, 5.0), 1.0);
+        lamp.mat.ambient = 0.0;
+        lamp.mat.emission = Color::new(1.0, 0.5, 0.0);
+        lamp.mat.emission_strength = 2.0;
+
+        let mut world = World::new();
+        world.add_object(lamp);
+
+        // no lights at all
+        let ray = Ray::new(point(0.0, 0.0, 0.0), vector(0.0, 0.0, 1.0));
+        assert_almost_eq_color(world.shade(&ray), Color::new(2.0, 1.0, 0.0));
+    }
+
+    #[test]
+    fn emissive_surface_seen_in_mirror() {
+        let mut world = mirror_world(1.0);
+        world.objects.pop();
+        let mut lamp = Sphere::new(point(0.0, 2.0, 1.0), 0.5);
+        lamp.mat.ambient = 0.0;
+        lamp.mat.emission = Color::new(0.0, 1.0, 0.0);
+        world.add_object(lamp);
+
+        assert_almost_eq_color(world.shade(&floor_ray()), Color::new(0.1, 1.1, 0.1));
+    }
+
+    #[test]
+    fn emissive_surface_lights_the_scene_with_indirect_samples() {
+        // a glowing ceiling over a floor, and no lights
+        let mut world = World::new();
+        let mut floor = Aabb::new(point(-10.0, -1.0, -10.0), point(10.0, 0.0, 10.0), true);
+        floor.mat.ambient = 0.0;
+        floor.mat.specular = 0.0;
+        world.add_object(floor);
+        let mut ceiling = Aabb::new(point(-100.0, 1.0, -100.0), point(100.0, 2.0, 100.0), true);
+        ceiling.mat.ambient = 0.0;
+        ceiling.mat.diffuse = 0.0;
+        ceiling.mat.specular = 0.0;
+        ceiling.mat.emission = Color::WHITE;
+        world.add_object(ceiling);
+
+        let ray = Ray::new(point(0.0, 0.5, -1.0), vector(0.0, -1.0, 2.0).normalize());
+        assert_almost_eq_color(world.shade(&ray), Color::BLACK);
+
+        // the ceiling fills almost all of the floor's sky: it looks lit by a light of the emission
+        world.indirect_samples = 64;
+        let lit = world.shade(&ray);
+        assert!((lit.red - 0.9).abs() < 0.1, "{:?}", lit);
+    }
+
+    #[test]
+    fn clear_coat_reflects_by_fresnel() {
+        let mut world = mirror_world(0.0);
+        world.objects[0] = Box::new({
+            let mut floor = Aabb::new(point(-10.0, -1.0, -10.0), point(10.0, 0.0, 10.0), true);
+            floor.mat.ambient = 0.1;
+            floor.mat.clearcoat = Some(ClearCoat::default());
+            floor
+        });
+
+        // 45 degrees onto glass-like lacquer: schlick gives about 5% of the red ball
+        let color = world.shade(&floor_ray());
+        let fresnel = ClearCoat::default().fresnel(vector(0.0, 1.0, -1.0).normalize(), vector(0.0, 1.0, 0.0));
+        assert!(fresnel > 0.04 && fresnel < 0.06);
+        assert_almost_eq_color(color, Color::new(0.1 + fresnel, 0.1, 0.1));
+    }
+
+    #[test]
+    fn bump_map_changes_lighting() {
+        let floor_world = |bump: Option<BumpMap>| {
+            let mut floor = Aabb::new(point(-1.0, -1.0, -1.0), point(1.0, 0.0, 1.0), true);
+            floor.mat.ambient = 0.0;
+            floor.mat.specular = 0.0;
+            floor.mat.normal_mapping = bump.map(|b| Box::new(b) as Box<dyn NormalMapping>);
+
+            let mut world = World::new();
+            world.add_object(floor);
+            world.add_light(PointLight::new(point(0.0, 10.0, 0.0), Color::WHITE));
+            world
+        };
+
+        let ray = Ray::new(point(0.0, 5.0, 0.0), vector(0.0, -1.0, 0.0));
+        let flat = floor_world(None).shade(&ray);
+        // 45 degree slope: the light hits at cos 45
+        let bumped = floor_world(Some(BumpMap::new(|p| p[0], 1.0))).shade(&ray);
+        assert_almost_eq_color(bumped, flat * std::f32::consts::FRAC_1_SQRT_2);
+    }
+
+    #[test]
+    fn subsurface_light_bleeds_into_shadow() {
+        let subsurface = Subsurface::from_albedo(Color::WHITE * 0.99, Color::WHITE * 0.05).unwrap();
+        let toon = Toon {
+            rim: 0.0,
+            ..Default::default()
+        };
+        // the shading model's own diffuse light is left out for every model
+        for bsdf in [Box::new(Phong) as Box<dyn Bsdf>, Box::new(toon)] {
+            let mut floor = Aabb::new(point(-5.0, -1.0, -5.0), point(5.0, 0.0, 5.0), true);
+            floor.mat.ambient = 0.0;
+            floor.mat.specular = 0.0;
+            floor.mat.bsdf = bsdf;
+            floor.mat.subsurface = Some(subsurface);
+
+            // casts a shadow on the floor for x < 0
+            let blocker = Aabb::new(point(-10.0, 1.0, -10.0), point(0.0, 1.1, 10.0), true);
+
+            let mut world = World::new();
+            world.add_object(floor);
+            world.add_object(blocker);
+            world.add_light(PointLight::new(point(0.0, 10.0, 0.0), Color::WHITE));
+
+            let shade_at =
+                |x: f32| world.shade(&Ray::new(point(x - 0.5, 0.5, 0.0), vector(1.0, -1.0, 0.0).normalize()));
+            assert!(shade_at(-0.05).red > 0.02);
+            assert!(shade_at(-2.0).red < 1e-3);
+
+            // far from the shadow the floor looks like a diffuse surface of the total reflectance,
+            // less the few percent the boundary reflects on the way in and out
+            let lit = shade_at(2.0).red;
+            let expected = subsurface.diffuse_reflectance().red * 0.96;
+            assert!((lit - expected).abs() < 0.1 * expected, "{} vs {}", lit, expected);
+        }
+    }
+
+    /// mirror floor at y = 0 and a red ambient-only sphere above it, seen only in the reflection
+    fn mirror_world(reflective: f32) -> World {
+        let mut floor = Aabb::new(point(-10.0, -1.0, -10.0), point(10.0, 0.0, 10.0), true);
+        floor.mat.ambient = 0.1;
+        floor.mat.reflective = reflective;
+
+        let mut ball = Sphere::new(point(0.0, 2.0, 1.0), 0.5);
+        ball.mat.color = Color::new(1.0, 0.0, 0.0);
+        ball.mat.ambient = 1.0;
+
+        let mut world = World::new();
+        world.add_object(floor);
+        world.add_object(ball);
+        world
+    }
+
+    fn floor_ray() -> Ray {
+        // hits the floor at (0, 0, -1), reflects straight into the sphere
+        Ray::new(point(0.0, 1.0, -2.0), vector(0.0, -1.0, 1.0).normalize())
+    }
+
+    #[test]
+    fn non_reflective_surface() {
+        let world = mirror_world(0.0);
+        let ray = floor_ray();
+        assert_almost_eq_color(world.shade(&ray), Color::new(0.1, 0.1, 0.1));
+    }
+
+    #[test]
+    fn reflective_surface() {
+        let world = mirror_world(0.5);
+        let ray = floor_ray();
+        assert_almost_eq_color(world.shade(&ray), Color::new(0.6, 0.1, 0.1));
+    }
+
+    #[test]
+    fn reflection_stops_at_max_depth() {
+        let mut world = mirror_world(0.5);
+        world.max_depth = 0;
+        let ray = floor_ray();
+        assert_almost_eq_color(world.shade(&ray), Color::new(0.1, 0.1, 0.1));
+    }
+
+    #[test]
+    fn parallel_mirrors_terminate() {
+        let mut world = World::new();
+        for y in [-1.0, 1.0] {
+            let mut mirror = Aabb::new(point(-10.0, y - 0.1, -10.0), point(10.0, y + 0.1, 10.0), true);
+            mirror.mat.reflective = 1.0;
+            world.add_object(mirror);
+        }
+        world.enable_stats(true);
+
+        let ray = Ray::new(point(0.0, 0.0, 0.0), vector(0.0, 1.0, 0.0));
+        world.shade(&ray);
+        assert_eq!(world.take_stats().secondary_rays, world.max_depth as u64);
+    }
+
+    /// glass slab between y = -0.1 and y = 0, red ambient-only ball below it
+    fn glass_slab_world(transparency: f32) -> World {
+        let mut slab = Aabb::new(point(-10.0, -0.1, -10.0), point(10.0, 0.0, 10.0), true);
+        slab.mat.ambient = 0.1;
+        slab.mat.transparency = transparency;
+        slab.mat.refractive_index = 1.5;
+
+        let mut ball = Sphere::new(point(0.0, -2.0, 0.0), 0.5);
+        ball.mat.color = Color::new(1.0, 0.0, 0.0);
+        ball.mat.ambient = 1.0;
+
+        let mut world = World::new();
+        world.add_object(slab);
+        world.add_object(ball);
+        world
+    }
+
+    #[test]
+    fn opaque_surface_does_not_refract() {
+        let world = glass_slab_world(0.0);
+        let ray = Ray::new(point(0.0, 1.0, 0.0), vector(0.0, -1.0, 0.0));
+        assert_almost_eq_color(world.shade(&ray), Color::new(0.1, 0.1, 0.1));
+    }
+
+    #[test]
+    fn refraction_through_slab() {
+        let world = glass_slab_world(0.5);
+        let ray = Ray::new(point(0.0, 1.0, 0.0), vector(0.0, -1.0, 0.0));
+        // top face: 0.1 + 0.5 * (bottom face from inside: 0.1 + 0.5 * ball)
+        assert_almost_eq_color(world.shade(&ray), Color::new(0.4, 0.15, 0.15));
+    }
+
+    #[test]
+    fn oblique_ray_refracts_through_both_faces() {
+        let world = glass_slab_world(1.0);
+        world.enable_stats(true);
+        let ray = Ray::new(point(-1.0, 1.0, 0.0), vector(1.0, -1.0, 0.0).normalize());
+        world.shade(&ray);
+        // enters top face, exits bottom face
+        assert_eq!(world.take_stats().secondary_rays, 2);
+    }
+
+    #[test]
+    fn flat_dispersion_splits_channels_back_to_rgb() {
+        let mut world = glass_slab_world(0.5);
+        let mut slab = Aabb::new(point(-10.0, -0.1, -10.0), point(10.0, 0.0, 10.0), true);
+        slab.mat.ambient = 0.1;
+        slab.mat.transparency = 0.5;
+        slab.mat.dispersion = Some(Dispersion::Cauchy { a: 1.5, b: 0.0 });
+        world.objects[0] = Box::new(slab);
+
+        // same index for every wavelength: same as refraction_through_slab
+        let ray = Ray::new(point(0.0, 1.0, 0.0), vector(0.0, -1.0, 0.0));
+        assert_almost_eq_color(world.shade(&ray), Color::new(0.4, 0.15, 0.15));
+    }
+
+    #[test]
+    fn dispersive_slab_traces_each_channel() {
+        let mut world = glass_slab_world(1.0);
+        let mut slab = Aabb::new(point(-10.0, -0.1, -10.0), point(10.0, 0.0, 10.0), true);
+        slab.mat.transparency = 1.0;
+        slab.mat.dispersion = Some(Dispersion::BK7);
+        world.objects[0] = Box::new(slab);
+        world.enable_stats(true);
+
+        let ray = Ray::new(point(-1.0, 1.0, 0.0), vector(1.0, -1.0, 0.0).normalize());
+        world.shade(&ray);
+        // oblique_ray_refracts_through_both_faces, once per channel
+        assert_eq!(world.take_stats().secondary_rays, 6);
+    }
+
+    #[test]
+    fn thin_film_tints_reflection() {
+        let film = ThinFilm {
+            thickness: 300.0,
+            refractive_index: 1.33,
+        };
+        let mut world = mirror_world(0.0);
+        world.objects[0] = Box::new({
+            let mut floor = Aabb::new(point(-10.0, -1.0, -10.0), point(10.0, 0.0, 10.0), true);
+            floor.mat.ambient = 0.1;
+            floor.mat.thin_film = Some(film);
+            floor
+        });
+
+        // soap film on an opaque floor: the red ball shows by the film's red reflectance
+        let reflectance = film.reflectance_color(std::f32::consts::FRAC_1_SQRT_2, 1.0, 1.0, None);
+        assert_almost_eq_color(world.shade(&floor_ray()), Color::new(0.1 + reflectance.red, 0.1, 0.1));
+    }
+
+    #[test]
+    fn total_internal_reflection_is_black() {
+        let mut world = World::new();
+        let mut glass = Sphere::new(point(0.0, 0.0, 0.0), 1.0);
+        glass.mat.ambient = 0.0;
+        glass.mat.diffuse = 0.0;
+        glass.mat.specular = 0.0;
+        glass.mat.transparency = 1.0;
+        glass.mat.refractive_index = 1.5;
+        world.add_object(glass);
+
+        // starts inside, hits the surface at a grazing angle
+        let ray = Ray::new(point(0.0, 0.0, f32::sqrt(2.0) / 2.0), vector(0.0, 1.0, 0.0));
+        assert_almost_eq_color(world.shade(&ray), Color::BLACK);
+    }
+
+    #[test]
+    fn stats_count_rays_and_tests() {
+        let world = World::default();
+        let ray = Ray::new(point(0.0, 0.0, -5.0), vector(0.0, 0.0, 1.0));
+
+        world.shade(&ray);
+        assert_eq!(world.take_stats().primary_rays, 0, "disabled by default");
+
+        world.enable_stats(true);
+        world.shade(&ray);
+        let stats = world.take_stats();
+
+        assert_eq!(stats.primary_rays, 1);
+        assert_eq!(stats.shadow_rays, 1);
+        assert_eq!(stats.secondary_rays, 0);
+        // primary + shadow ray against both spheres
+        assert_eq!(stats.intersection_tests["sphere"], 4);
+        assert_eq!(stats.peak_intersections, 4);
+
+        assert_eq!(world.take_stats().primary_rays, 0, "take resets");
     }
 }